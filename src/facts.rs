@@ -0,0 +1,156 @@
+use crate::service::Service;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// The probes run on the context; each prints a `key=value` line, so that a failing probe does not affect the others.
+const PROBES: &str = "echo \"kernel=$(uname -s)\"; \
+    echo \"kernelRelease=$(uname -r)\"; \
+    echo \"architecture=$(uname -m)\"; \
+    echo \"hostname=$(hostname 2>/dev/null || cat /proc/sys/kernel/hostname)\"; \
+    echo \"cpus=$(getconf _NPROCESSORS_ONLN 2>/dev/null || nproc)\"; \
+    echo \"memory=$(awk '/^MemTotal:/ { print $2 }' /proc/meminfo 2>/dev/null)\"; \
+    echo \"init=$(cat /proc/1/comm 2>/dev/null)\"; \
+    if command -v openrc >/dev/null 2>&1; then echo \"openrc=true\"; fi";
+
+/// Gathers facts about the context.
+///
+/// The OS release is read from `/etc/os-release`; kernel, architecture, hostname, CPU count, memory (in kB), init system and network interfaces are probed with shell commands. Facts that cannot be determined are `null`.
+pub async fn gather(context: &mut Box<dyn Service>) -> Result<Value, Box<dyn std::error::Error>> {
+    let os_release = match context.file_read("/etc/os-release".to_string()).await {
+        Ok(data) => parse_os_release(&*String::from_utf8_lossy(&data)),
+        Err(_) => HashMap::new()
+    };
+    let probes = parse_probes(&*context.query(PROBES.to_string()).await?.stdout);
+    let interfaces = parse_interfaces(&*context.query("ip -o addr show 2>/dev/null || true".to_string()).await?.stdout);
+    let probe = |key: &str| probes.get(key).filter(|value| !value.is_empty()).cloned().map(Value::String).unwrap_or(Value::Null);
+    let number = |key: &str| probes.get(key).and_then(|value| value.parse::<u64>().ok()).map(Value::from).unwrap_or(Value::Null);
+    let os_value = |key: &str| os_release.get(key).cloned().map(Value::String).unwrap_or(Value::Null);
+
+    Ok(json!({
+        "os": {
+            "id": os_value("ID"),
+            "name": os_value("NAME"),
+            "prettyName": os_value("PRETTY_NAME"),
+            "version": os_value("VERSION"),
+            "versionId": os_value("VERSION_ID"),
+            "family": get_os_family(&os_release).map(Value::from).unwrap_or(Value::Null),
+        },
+        "kernel": {
+            "name": probe("kernel"),
+            "release": probe("kernelRelease"),
+        },
+        "architecture": probe("architecture"),
+        "hostname": probe("hostname"),
+        "cpus": number("cpus"),
+        "memory": number("memory"),
+        "initSystem": get_init_system(probes.get("init").map(|init| &**init), probes.contains_key("openrc")).map(Value::from).unwrap_or(Value::Null),
+        "interfaces": interfaces,
+    }))
+}
+
+#[test]
+fn function_parse_os_release() {
+    let os_release = parse_os_release("NAME=\"Debian GNU/Linux\"\nVERSION_ID='11'\n# comment\nID=debian\n\nHOME_URL=\"https://www.debian.org/\"\n");
+
+    assert_eq!(os_release["NAME"], "Debian GNU/Linux");
+    assert_eq!(os_release["VERSION_ID"], "11");
+    assert_eq!(os_release["ID"], "debian");
+    assert_eq!(os_release.len(), 4);
+}
+
+/// Parses the `KEY=value` lines of `/etc/os-release`, removing quotes around the values.
+fn parse_os_release(content: &str) -> HashMap<String, String> {
+    let mut entries = HashMap::new();
+
+    for line in content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim();
+            let value = match (value.chars().next(), value.chars().last()) {
+                (Some('"'), Some('"')) | (Some('\''), Some('\'')) if value.len() >= 2 => &value[1..value.len() - 1],
+                _ => value
+            };
+
+            entries.insert(key.into(), value.replace("\\\"", "\""));
+        }
+    }
+
+    entries
+}
+
+fn parse_probes(output: &str) -> HashMap<String, String> {
+    output.lines().filter_map(|line| line.split_once('=')).map(|(key, value)| (key.to_string(), value.trim().to_string())).collect()
+}
+
+#[test]
+fn function_get_os_family() {
+    assert_eq!(get_os_family(&parse_os_release("ID=ubuntu\nID_LIKE=debian\n")), Some("debian".into()));
+    assert_eq!(get_os_family(&parse_os_release("ID=rocky\nID_LIKE=\"rhel centos fedora\"\n")), Some("redhat".into()));
+    assert_eq!(get_os_family(&parse_os_release("ID=fedora\n")), Some("redhat".into()));
+    assert_eq!(get_os_family(&parse_os_release("ID=alpine\n")), Some("alpine".into()));
+    assert_eq!(get_os_family(&parse_os_release("ID=nixos\n")), Some("nixos".into()));
+    assert_eq!(get_os_family(&parse_os_release("")), None);
+}
+
+/// Determines the family of the OS from its id and the ids it is similar to.
+fn get_os_family(os_release: &HashMap<String, String>) -> Option<String> {
+    let id = os_release.get("ID")?;
+    let ids: Vec<&str> = std::iter::once(&**id).chain(os_release.get("ID_LIKE").map(|like| like.split_whitespace().collect::<Vec<&str>>()).unwrap_or_default()).collect();
+    let families = [
+        ("debian", &["debian", "ubuntu"][..]),
+        ("redhat", &["rhel", "fedora", "centos"][..]),
+        ("arch", &["arch"][..]),
+        ("alpine", &["alpine"][..]),
+        ("suse", &["suse", "opensuse", "sles"][..]),
+    ];
+
+    for (family, members) in &families {
+        if ids.iter().any(|id| members.contains(id)) {
+            return Some(family.to_string());
+        }
+    }
+
+    Some(id.clone())
+}
+
+fn get_init_system(init: Option<&str>, openrc: bool) -> Option<String> {
+    match init? {
+        "" => None,
+        "init" if openrc => Some("openrc".into()),
+        "init" => Some("sysvinit".into()),
+        init => Some(init.into())
+    }
+}
+
+#[test]
+fn function_parse_interfaces() {
+    let interfaces = parse_interfaces("1: lo    inet 127.0.0.1/8 scope host lo\\       valid_lft forever preferred_lft forever\n\
+        1: lo    inet6 ::1/128 scope host \\       valid_lft forever preferred_lft forever\n\
+        2: eth0@if5    inet 10.0.0.2/24 brd 10.0.0.255 scope global eth0\\       valid_lft forever preferred_lft forever\n");
+
+    assert_eq!(interfaces, json!([
+        {"name": "lo", "addresses": ["127.0.0.1/8", "::1/128"]},
+        {"name": "eth0", "addresses": ["10.0.0.2/24"]}
+    ]));
+}
+
+/// Parses the output of `ip -o addr show` into a list of interfaces with their addresses.
+fn parse_interfaces(output: &str) -> Value {
+    let mut interfaces: Vec<(String, Vec<String>)> = Vec::new();
+
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        if fields.len() < 4 || !(fields[2] == "inet" || fields[2] == "inet6") {
+            continue;
+        }
+
+        let name = fields[1].split('@').next().unwrap_or(fields[1]);
+
+        match interfaces.iter_mut().find(|(existing, _)| existing == name) {
+            Some((_, addresses)) => addresses.push(fields[3].into()),
+            None => interfaces.push((name.into(), vec![fields[3].into()]))
+        }
+    }
+
+    Value::Array(interfaces.into_iter().map(|(name, addresses)| json!({"name": name, "addresses": addresses})).collect())
+}
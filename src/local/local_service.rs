@@ -1,23 +1,57 @@
-use crate::service::Service;
+use crate::service::{Service, Request, Response, DirEntry, CommandOutput, is_not_found};
 use async_trait::async_trait;
 use super::session::Session;
-use tokio::fs::{read, write};
+use super::error::LocalError;
+use tokio::fs::{read, write, read_dir, create_dir, remove_dir, remove_file, set_permissions};
+use std::os::unix::fs::PermissionsExt;
+use std::time::Instant;
 
 pub struct LocalService {
-    session: Session
+    session: Session,
+    check_mode: bool,
+    deadline: Option<Instant>,
+    temp_files: Vec<String>
 }
 
 impl LocalService {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        Ok(LocalService {session: Session::new()})
+    pub fn new(check_mode: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(LocalService {session: Session::new(), check_mode, deadline: None, temp_files: Vec::new()})
+    }
+
+    fn refuse_in_check_mode(&self, operation: &str) -> Result<(), LocalError> {
+        match self.check_mode {
+            true => Err(LocalError::new(&*format!("refusing to {} in check mode", operation))),
+            false => Ok(())
+        }
     }
 }
 
 #[async_trait]
 impl Service for LocalService {
-    async fn run(&mut self, command: String) -> Result<String, Box<dyn std::error::Error>> {
+    fn check_mode(&self) -> bool {
+        self.check_mode
+    }
+
+    fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    async fn query(&mut self, command: String) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        let args = ["bash", "-c", &*command];
+        let future = self.session.run_command(&args, false);
+
+        let output = match self.deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), future).await.map_err(|_| LocalError::new("command timed out"))??,
+            None => future.await?
+        };
+
+        output.into_result()
+    }
+
+    async fn run(&mut self, command: String) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("run command")?;
         /// TODO: add sudo
-        self.session.run_command(&["bash", "-c", &*command], false).await
+        self.query(command).await
     }
 
     async fn file_read(&mut self, path: String) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
@@ -25,6 +59,76 @@ impl Service for LocalService {
     }
 
     async fn file_write(&mut self, path: String, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("write file")?;
         Ok(write(path, data).await?)
     }
+
+    async fn dir_read(&mut self, path: String) -> Result<Vec<DirEntry>, Box<dyn std::error::Error>> {
+        let mut dir = read_dir(path).await?;
+        let mut entries = Vec::new();
+
+        while let Some(entry) = dir.next_entry().await? {
+            let metadata = entry.metadata().await?;
+
+            entries.push(DirEntry {
+                name: entry.file_name().to_string_lossy().into(),
+                is_dir: metadata.is_dir(),
+                size: metadata.len(),
+                mode: metadata.permissions().mode() & 0o7777
+            });
+        }
+
+        Ok(entries)
+    }
+
+    async fn dir_create(&mut self, path: String) -> Result<(), Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("create directory")?;
+        Ok(create_dir(path).await?)
+    }
+
+    async fn dir_remove(&mut self, path: String) -> Result<(), Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("remove directory")?;
+        Ok(remove_dir(path).await?)
+    }
+
+    async fn file_remove(&mut self, path: String) -> Result<(), Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("remove file")?;
+        remove_file(&path).await?;
+        self.temp_files.retain(|temp_file| *temp_file != path);
+
+        Ok(())
+    }
+
+    async fn file_set_mode(&mut self, path: String, mode: u32) -> Result<(), Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("set mode")?;
+        Ok(set_permissions(path, std::fs::Permissions::from_mode(mode)).await?)
+    }
+
+    async fn socket_request(&mut self, socket: String, request: Request) -> Result<Response, Box<dyn std::error::Error>> {
+        if request.method != "GET" {
+            self.refuse_in_check_mode("send request")?;
+        }
+
+        self.session.socket_request(&*socket, request).await
+    }
+
+    fn add_temp_file(&mut self, path: String) {
+        self.temp_files.push(path);
+    }
+
+    async fn remove_temp_files(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut failed = Vec::new();
+
+        for path in std::mem::take(&mut self.temp_files) {
+            match self.file_remove(path.clone()).await {
+                Err(err) if !is_not_found(err.as_ref()) => failed.push(format!("\"{}\" ({})", path, err)),
+                _ => {}
+            }
+        }
+
+        match failed.is_empty() {
+            true => Ok(()),
+            false => Err(LocalError::new(&*format!("could not remove {}", failed.join(", "))).into())
+        }
+    }
 }
@@ -1,9 +1,12 @@
 use std::error::Error;
+use std::os::unix::process::ExitStatusExt;
 use std::process::Stdio;
 use rpassword;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
 use tokio::process::Command;
 use super::error::LocalError;
+use crate::service::{Request, Response, CommandOutput};
 
 pub struct Session {
 }
@@ -13,7 +16,7 @@ impl Session {
         Session {}
     }
 
-    pub async fn run_command(&mut self, command: &[&str], sudo: bool) -> Result<String, Box<dyn Error>> {
+    pub async fn run_command(&mut self, command: &[&str], sudo: bool) -> Result<CommandOutput, Box<dyn Error>> {
         let mut cmd = match sudo {
             true => {
                 let mut cmd = Command::new("sudo");
@@ -34,32 +37,84 @@ impl Session {
         cmd.stderr(Stdio::piped());
         cmd.stdin(Stdio::piped());
         cmd.stdout(Stdio::piped());
+        // the child runs in a process group of its own, which is killed if the command is cancelled (e.g. by a timeout)
+        cmd.process_group(0);
+        cmd.kill_on_drop(true);
     
         let mut child = cmd.spawn()?;
+        let mut guard = ProcessGroupGuard { pgid: child.id().map(|id| id as libc::pid_t) };
+        let mut stdout = child.stdout.take().unwrap();
         let mut stderr = child.stderr.take().unwrap();
         let mut stdin = child.stdin.take().unwrap();
-        let hndl = tokio::spawn(async move {
-            child.wait_with_output().await.unwrap()
-        });
-    
-        let mut data = [0; 15];
-        stderr.read(&mut data).await?;
-        let mut err_string = std::string::String::from_utf8(data.to_vec())?;
+        let mut out_data = Vec::new();
+        let read_stdout = stdout.read_to_end(&mut out_data);
+        let read_stderr = async {
+            let mut data = [0; 15];
+            let bytes_read = stderr.read(&mut data).await?;
+            let mut err_string = std::string::String::from_utf8(data[..bytes_read].to_vec())?;
+        
+            if err_string.starts_with("[sudo] password") {
+                let pass = rpassword::prompt_password_stdout("enter sudo password: ")? + "\n";
+                stdin.write_all(pass.as_bytes()).await?;
+            }
+        
+            let mut err_string2 = String::new();
+            stderr.read_to_string(&mut err_string2).await?;
+        
+            err_string += err_string2.as_str();
+            Ok::<String, Box<dyn Error + Send + Sync>>(err_string)
+        };
+        let (read_stdout, err_string) = tokio::join!(read_stdout, read_stderr);
+
+        read_stdout?;
+
+        let err_string = err_string.map_err(|err| LocalError::new(&*err.to_string()))?;
+        let status = child.wait().await?;
+
+        guard.pgid = None;
     
-        if err_string.starts_with("[sudo] password") {
-            let pass = rpassword::prompt_password_stdout("enter sudo password: ")? + "\n";
-            stdin.write(pass.as_bytes()).await?;
+        // a process terminated by a signal is reported with the exit code used by shells
+        let exit_code = status.code().or_else(|| status.signal().map(|signal| 128 + signal)).unwrap_or(-1);
+
+        Ok(CommandOutput { stdout: String::from_utf8(out_data)?, stderr: err_string, exit_code })
+    }
+
+    pub async fn socket_request(&mut self, socket: &str, request: Request) -> Result<Response, Box<dyn Error>> {
+        let stream = UnixStream::connect(socket).await?;
+        let (mut sender, connection) = hyper::client::conn::handshake(stream).await?;
+
+        tokio::spawn(connection);
+
+        let mut builder = hyper::Request::builder()
+            .method(request.method.as_str())
+            .uri(request.path.as_str())
+            .header("Host", "localhost");
+
+        for (name, value) in request.headers {
+            builder = builder.header(name.as_str(), value.as_str());
         }
-    
-        let mut err_string2 = String::new();
-        stderr.read_to_string(&mut err_string2).await?;
-    
-        err_string += err_string2.as_str();
-        let output = hndl.await?;
-    
-        match output.status.success() {
-            true => Ok(String::from_utf8(output.stdout)?),
-            false => Err(LocalError::new(&*err_string).into())
+
+        let req = builder.body(match request.body {
+            Some(vec) => hyper::Body::from(vec),
+            None => hyper::Body::empty()
+        })?;
+        let resp = sender.send_request(req).await?;
+        let status = resp.status().as_u16();
+        let body = hyper::body::to_bytes(resp).await?.to_vec();
+
+        Ok(Response { status, body })
+    }
+}
+
+/// Kills a process group when dropped, unless the group id has been reset.
+struct ProcessGroupGuard {
+    pgid: Option<libc::pid_t>,
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.pgid {
+            unsafe { libc::kill(-pgid, libc::SIGKILL) };
         }
     }
 }
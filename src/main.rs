@@ -3,14 +3,30 @@ use ssh::ssh_service;
 mod local;
 use local::local_service;
 use tokio::fs;
+use tokio::sync::{mpsc, Semaphore};
 use serde_json::{Value};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 mod service;
 use service::Service;
 mod error;
 use error::InfcoError;
-use log::{error, info};
+use log::{error, info, warn};
 mod task;
-use task::{command, file_transfer};
+use task::{command, file_transfer, template, line_in_file, block_in_file, package, systemd, docker_container, user, group, unarchive, script};
+use task::outcome::{HostOutcome, Status, TaskOutcome, TaskResult};
+mod templating;
+use templating::{expression, renderer};
+mod report;
+use report::Report;
+mod state;
+use state::{HostState, State};
+mod facts;
+mod tasks_file;
+use tasks_file::TasksFile;
+mod selection;
+use selection::{matches_limit, TaskSelection};
 
 #[tokio::main]
 pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -19,61 +35,630 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     if let Some(matches) = matches.subcommand_matches("process") {
         let hosts: Value = serde_json::from_str(fs::read_to_string(matches.value_of("hosts").unwrap()).await?.as_str())?;
-        let tasks: Value = serde_json::from_str(fs::read_to_string(matches.value_of("tasks").unwrap()).await?.as_str())?;
+        let tasks_file = TasksFile::load(matches.value_of("tasks").unwrap())?;
+        let tasks = &tasks_file.content;
+        let tasks_hash = tasks_file.hash();
+
+        let check_mode = matches.is_present("check");
+        let timeout = match matches.value_of("timeout") {
+            Some(value) => Some(parse_timeout(value.parse().ok())?),
+            None => None
+        };
+        let forks: usize = matches.value_of("forks").unwrap().parse().map_err(|_| InfcoError::new("forks must be a positive number"))?;
+
+        if forks == 0 {
+            return Err(InfcoError::new("forks must be a positive number").into());
+        }
+
+        let max_fail_percentage: f64 = match matches.value_of("max-fail-percentage") {
+            Some(value) => value.parse().ok().filter(|percentage| (0.0..=100.0).contains(percentage)).ok_or(InfcoError::new("max fail percentage must be a number between 0 and 100"))?,
+            None if matches.is_present("fail-fast") => 0.0,
+            None => 100.0
+        };
+        let reports = match matches.values_of("report") {
+            Some(values) => values.map(Report::parse).collect::<Result<Vec<Report>, InfcoError>>()?,
+            None => Vec::new()
+        };
+
+        if check_mode {
+            info!("running in check mode");
+        }
 
         let task_tags: Vec<&str> = tasks["tags"].as_array().unwrap().iter().map(|entry| entry.as_str().unwrap()).collect();
+        let limit = get_values(matches, "limit");
+        let mut matching_hosts = Vec::new();
 
         for host in hosts["hosts"].as_array().unwrap() {
             let host_tags: Vec<&str> = host["tags"].as_array().unwrap().iter().map(|entry| entry.as_str().unwrap()).collect();
 
-            match vecs_have_common_entries(&task_tags, &host_tags) {
-                true => {
-                    info!("processing host {}", host["title"]);
-                    process_tasks_for_host(&tasks["tasks"].as_array().unwrap(), host).await?;
-                },
+            match vecs_have_common_entries(&task_tags, &host_tags) && matches_limit(&*host_title(host), &limit) {
+                true => matching_hosts.push(host.clone()),
                 false => info!("skipping host {}", host["title"])
             }
         }
+
+        let selection = TaskSelection { tags: get_values(matches, "tags"), skip_tags: get_values(matches, "skip-tags") };
+        let state_path = match (matches.value_of("state"), matches.is_present("no-state")) {
+            (_, true) => None,
+            (Some(path), false) => Some(path.to_string()),
+            (None, false) => Some(get_default_state_path(matches.value_of("tasks").unwrap()))
+        };
+        let state = match matches.value_of("resume") {
+            Some(path) => State::load(path, &tasks_hash, selection.to_value(&limit))?,
+            None => State::new(state_path.as_deref(), &tasks_hash, selection.to_value(&limit))
+        };
+
+        check_tasks(&tasks_file.tasks, &tasks_file.handlers)?;
+
+        let gather_facts = match &tasks["gatherFacts"] {
+            Value::Null => true,
+            Value::Bool(gather_facts) => *gather_facts,
+            _ => return Err(InfcoError::new("gatherFacts must be a boolean").into())
+        };
+        let settings = RunSettings { check_mode, timeout, forks, max_fail_percentage, gather_facts };
+        let exit_code = tokio::task::LocalSet::new().run_until(
+            process_hosts(tasks_file.tasks, tasks_file.handlers, matching_hosts, settings, selection, state, &reports)
+        ).await?;
+
+        if exit_code != 0 {
+            std::process::exit(exit_code);
+        }
     } else if let Some(matches) = matches.subcommand_matches("fingerprint") {
         let host = matches.value_of("host").unwrap();
         let user = matches.value_of("user").unwrap();
 
         println!("{}", ssh_service::SshService::get_server_fingerprint(host, user)?);
+    } else if let Some(matches) = matches.subcommand_matches("facts") {
+        let hosts: Value = serde_json::from_str(fs::read_to_string(matches.value_of("hosts").unwrap()).await?.as_str())?;
+        let limit = get_values(matches, "limit");
+        let mut host_facts = serde_json::Map::new();
+
+        for host in hosts["hosts"].as_array().unwrap().iter().filter(|host| matches_limit(&*host_title(host), &limit)) {
+            let result = match create_context(host, true) {
+                Ok(mut context) => facts::gather(&mut context).await,
+                Err(err) => Err(err)
+            };
+
+            host_facts.insert(host_title(host), match result {
+                Ok(facts) => facts,
+                Err(err) => {
+                    error!("[{}] {}", host_title(host), err);
+                    serde_json::json!({"error": err.to_string()})
+                }
+            });
+        }
+
+        println!("{}", serde_json::to_string_pretty(&host_facts)?);
     }
 
     Ok(())
 }
 
-async fn process_tasks_for_host(tasks: &Vec<Value>, host: &Value) -> Result<(), Box<dyn std::error::Error>> {
-    let mut context: Box<dyn Service> = match host["context"]["type"].as_str() {
-        Some("ssh") => {
-            let host_name = host["context"]["config"]["host"].as_str().ok_or(InfcoError::new("no host specified"))?;
-            let user_name = host["context"]["config"]["username"].as_str().ok_or(InfcoError::new("no user specified"))?;
-            let hash =host["context"]["config"]["serverPublicKeyHash"].as_str().ok_or(InfcoError::new("no hash specified"))?;
+/// The settings of a run; `timeout` is the default timeout of a task.
+#[derive(Clone, Copy)]
+struct RunSettings {
+    check_mode: bool,
+    timeout: Option<Duration>,
+    forks: usize,
+    max_fail_percentage: f64,
+    gather_facts: bool,
+}
+
+/// Processes the hosts concurrently, running at most `forks` hosts at the same time, and returns the exit code of the run.
+///
+/// The hosts share a single thread; the contexts are driven by asynchronous IO. Once the percentage of failed hosts exceeds `max_fail_percentage`, no further hosts are started; hosts already being processed are completed.
+/// The reports are written once all hosts are done.
+async fn process_hosts(tasks: Vec<Value>, handlers: Vec<Value>, hosts: Vec<Value>, settings: RunSettings, selection: TaskSelection, state: State, reports: &[Report]) -> Result<i32, Box<dyn std::error::Error>> {
+    let host_count = hosts.len();
+    let max_fail_percentage = settings.max_fail_percentage;
+    let tasks = Rc::new(tasks);
+    let handlers = Rc::new(handlers);
+    let selection = Rc::new(selection);
+    let state = Rc::new(RefCell::new(state));
+    let semaphore = Rc::new(Semaphore::new(settings.forks));
+    let failed_count = Rc::new(Cell::new(0));
+    let (result_tx, mut result_rx) = mpsc::unbounded_channel();
+
+    for host in hosts {
+        let tasks = tasks.clone();
+        let handlers = handlers.clone();
+        let selection = selection.clone();
+        let state = state.clone();
+        let semaphore = semaphore.clone();
+        let failed_count = failed_count.clone();
+        let result_tx = result_tx.clone();
+
+        tokio::task::spawn_local(async move {
+            let _permit = semaphore.acquire().await;
+
+            if exceeds_percentage(failed_count.get(), host_count, max_fail_percentage) {
+                warn!("[{}] not processed", host_title(&host));
+                result_tx.send(HostOutcome::not_processed(&host_title(&host))).ok();
+                return;
+            }
+
+            info!("[{}] processing host", host_title(&host));
+
+            let host_outcome = process_tasks_for_host(&tasks, &handlers, &host, settings, &selection, &state).await;
+
+            if host_outcome.is_failed() {
+                failed_count.set(failed_count.get() + 1);
+
+                if exceeds_percentage(failed_count.get(), host_count, max_fail_percentage) && !exceeds_percentage(failed_count.get() - 1, host_count, max_fail_percentage) {
+                    error!("{} of {} host(s) failed; no further hosts are processed", failed_count.get(), host_count);
+                }
+            }
+
+            result_tx.send(host_outcome).ok();
+        });
+    }
+
+    drop(result_tx);
+
+    let mut host_outcomes = Vec::new();
+
+    while let Some(host_outcome) = result_rx.recv().await {
+        if let Some(error) = &host_outcome.error {
+            error!("[{}] {}", host_outcome.title, error);
+        }
+
+        host_outcomes.push(host_outcome);
+    }
+
+    let state_writer = state.borrow_mut().close();
+
+    if let Some(state_writer) = state_writer {
+        state_writer.await.ok();
+    }
+
+    println!("recap");
+
+    for host_outcome in &host_outcomes {
+        println!("{}", host_outcome.recap());
+    }
+
+    let not_processed = host_outcomes.iter().filter(|host_outcome| !host_outcome.processed).count();
+
+    if not_processed > 0 {
+        println!("{} host(s) not processed", not_processed);
+    }
+
+    for report in reports {
+        report.write(&host_outcomes).await?;
+        info!("report written to {}", report.path);
+    }
+
+    Ok(get_exit_code(&host_outcomes))
+}
+
+fn exceeds_percentage(count: usize, total: usize, percentage: f64) -> bool {
+    count as f64 * 100.0 > total as f64 * percentage
+}
+
+#[test]
+fn function_get_exit_code() {
+    let mut failed = HostOutcome::new("a");
+    let mut unreachable = HostOutcome::new("b");
+
+    failed.tasks.push(TaskResult { title: "t".into(), task_type: "command".into(), outcome: TaskOutcome::failed("boom") });
+    unreachable.error = Some("no host specified".into());
+
+    assert_eq!(get_exit_code(&[HostOutcome::new("c")]), 0);
+    assert_eq!(get_exit_code(&[HostOutcome::new("c"), unreachable]), 4);
+    assert_eq!(get_exit_code(&[failed, HostOutcome::new("c")]), 2);
+}
+
+/// Returns 2 if a task failed on any host, 4 if a host could not be processed at all and 0 otherwise.
+fn get_exit_code(host_outcomes: &[HostOutcome]) -> i32 {
+    if host_outcomes.iter().any(|host_outcome| host_outcome.count(Status::Failed) > 0) {
+        2
+    } else if host_outcomes.iter().any(|host_outcome| host_outcome.error.is_some()) {
+        4
+    } else {
+        0
+    }
+}
+
+fn host_title(host: &Value) -> String {
+    host["title"].as_str().unwrap_or("unnamed host").to_string()
+}
+
+/// Processes the tasks for a host until all tasks are done or a task fails.
+///
+/// Unless disabled in the tasks file, the facts of the host are gathered before the first task.
+/// Tasks not included in the selection are left out. Handlers notified by a changed task are run once after all tasks succeeded, in the order in which they are defined.
+/// Unless in check mode, the progress is recorded in the state after every task; a host completed in a resumed run is skipped and a partially processed host continues with the task that failed.
+async fn process_tasks_for_host(tasks: &[Value], handlers: &[Value], host: &Value, settings: RunSettings, selection: &TaskSelection, state: &RefCell<State>) -> HostOutcome {
+    let mut host_outcome = HostOutcome::new(&*host_title(host));
+    let mut host_state = state.borrow().host(&host_outcome.title);
+    let update_state = |host_state: &HostState| {
+        if !settings.check_mode {
+            state.borrow_mut().update(&*host_title(host), host_state.clone());
+        }
+    };
+
+    if host_state.completed {
+        info!("[{}] completed in a previous run", host_outcome.title);
+        return host_outcome;
+    }
 
-            Box::new(ssh_service::SshService::new(host_name.into(), user_name.into(), hash.into())?)
+    let mut context = match create_context(host, settings.check_mode) {
+        Ok(context) => context,
+        Err(err) => {
+            host_outcome.error = Some(err.to_string());
+            return host_outcome;
+        }
+    };
+
+    let facts = match settings.gather_facts {
+        true => match facts::gather(&mut context).await {
+            Ok(facts) => facts,
+            Err(err) => {
+                host_outcome.error = Some(format!("error gathering facts: {}", err));
+                return host_outcome;
+            }
         },
-        Some("local") => Box::new(local_service::LocalService::new()?),
-        Some(name) => return Err(InfcoError::new(&*format!("unknown context type \"{}\"", name)).into()),
-        None => return Err(InfcoError::new("no context type found").into())
+        false => Value::Null
     };
+    let mut scope = get_scope(host, &facts, &host_state.registered);
 
-    for task in tasks {
-        info!("task {} ({})", task["title"], task["type"]);
-        match task["type"].as_str() {
-            Some("command") => {
-                command::run(&mut context, &task["config"]).await?;
-            },
-            Some("fileTransfer") => {
-                file_transfer::run(&mut context, &task["config"]).await?;
-            },
-            Some(name) => {
-                error!("unknown task type \"{}\"", name);
-                return Err(InfcoError::new(&*format!("unknown task type \"{}\"", name)).into())
+    if host_state.next_task > 0 {
+        info!("[{}] resuming with task {}", host_outcome.title, host_state.next_task + 1);
+    }
+
+    for (index, task) in tasks.iter().enumerate().skip(host_state.next_task) {
+        if !selection.includes(task) {
+            info!("[{}] task {} not selected", host_outcome.title, task["title"]);
+            continue;
+        }
+
+        info!("[{}] task {} ({})", host_outcome.title, task["title"], task["type"]);
+
+        let first_result = host_outcome.tasks.len();
+        let status = run_and_record_loop(&mut context, task, &apply_role_defaults(&scope, task), settings, &mut host_outcome).await;
+
+        if let Some(name) = task["register"].as_str() {
+            let value = get_registered_value(task, &host_outcome.tasks[first_result..]);
+
+            scope[name] = value.clone();
+            host_state.registered.insert(name.into(), value);
+        }
+
+        match status {
+            Status::Failed => return host_outcome,
+            Status::Changed => host_state.notified.extend(get_notifications(task).into_iter().map(String::from)),
+            _ => {}
+        }
+
+        host_state.next_task = index + 1;
+        update_state(&host_state);
+    }
+
+    for handler in handlers.iter().filter(|handler| host_state.notified.iter().any(|title| handler["title"].as_str() == Some(&**title))) {
+        info!("[{}] handler {} ({})", host_outcome.title, handler["title"], handler["type"]);
+
+        let first_result = host_outcome.tasks.len();
+        let status = run_and_record_loop(&mut context, handler, &apply_role_defaults(&scope, handler), settings, &mut host_outcome).await;
+
+        if let Some(name) = handler["register"].as_str() {
+            scope[name] = get_registered_value(handler, &host_outcome.tasks[first_result..]);
+        }
+
+        if status == Status::Failed {
+            return host_outcome;
+        }
+    }
+
+    update_state(&HostState { completed: true, next_task: tasks.len(), notified: Vec::new(), registered: host_state.registered });
+
+    host_outcome
+}
+
+#[test]
+fn function_get_registered_value() {
+    let results = vec![
+        TaskResult { title: "a (1)".into(), task_type: "command".into(), outcome: TaskOutcome::changed() },
+        TaskResult { title: "a (2)".into(), task_type: "command".into(), outcome: TaskOutcome::ok() },
+    ];
+
+    assert_eq!(get_registered_value(&serde_json::json!({}), &results[..1])["changed"], true);
+    assert_eq!(get_registered_value(&serde_json::json!({"loop": [1, 2]}), &results)["changed"], true);
+    assert_eq!(get_registered_value(&serde_json::json!({"loop": [1, 2]}), &results)["results"][1]["status"], "ok");
+}
+
+/// Returns the value registered for the results of a task; for a loop, the results of the items are collected in `results`.
+fn get_registered_value(task: &Value, results: &[TaskResult]) -> Value {
+    match (&task["loop"], results) {
+        (Value::Null, [result]) => result.outcome.to_value(),
+        _ => serde_json::json!({
+            "changed": results.iter().any(|result| result.outcome.status == Status::Changed),
+            "failed": results.iter().any(|result| result.outcome.status == Status::Failed || result.outcome.status == Status::Ignored),
+            "skipped": results.iter().all(|result| result.outcome.status == Status::Skipped),
+            "results": results.iter().map(|result| result.outcome.to_value()).collect::<Vec<Value>>()
+        })
+    }
+}
+
+/// Runs a task once or, if it has a `loop`, once per item with the item available as `item`; stops at the first failing item.
+async fn run_and_record_loop(context: &mut Box<dyn Service>, task: &Value, scope: &Value, settings: RunSettings, host_outcome: &mut HostOutcome) -> Status {
+    let items = match get_loop_items(task, scope) {
+        Ok(None) => return run_and_record_task(context, task, scope, settings, host_outcome).await,
+        Ok(Some(items)) => items,
+        Err(err) => {
+            record_task(task, scope, TaskOutcome::failed(&*err.to_string()), settings.check_mode, host_outcome);
+            return Status::Failed;
+        }
+    };
+    let mut status = Status::Ok;
+
+    for item in items {
+        let mut item_scope = scope.clone();
+
+        item_scope["item"] = item;
+
+        match run_and_record_task(context, task, &item_scope, settings, host_outcome).await {
+            Status::Failed => return Status::Failed,
+            Status::Changed => status = Status::Changed,
+            _ => {}
+        }
+    }
+
+    status
+}
+
+/// Runs a task, logs its outcome and adds it to the outcome of the host.
+///
+/// A failed task is retried according to its retry policy; if the task has an `until` condition, it is also retried until the condition holds. The condition is evaluated with the outcome available as `result`. In check mode, tasks are not retried.
+/// The timeout of the task applies to each attempt; temporary files an attempt left behind, e.g. because it timed out, are removed after it.
+async fn run_and_record_task(context: &mut Box<dyn Service>, task: &Value, scope: &Value, settings: RunSettings, host_outcome: &mut HostOutcome) -> Status {
+    let start = Instant::now();
+    let mut outcome = match get_retry_policy(task, settings.timeout) {
+        Ok(policy) => run_task_with_retries(context, task, scope, settings.check_mode, &policy, &host_outcome.title).await,
+        Err(err) => TaskOutcome::failed(&*err.to_string())
+    };
+
+    outcome.duration = start.elapsed();
+
+    if outcome.status == Status::Failed && task["ignoreErrors"].as_bool().unwrap_or(false) {
+        outcome.status = Status::Ignored;
+    }
+
+    record_task(task, scope, outcome, settings.check_mode, host_outcome)
+}
+
+#[tokio::test]
+async fn function_run_task_with_retries() {
+    let mut context: Box<dyn Service> = Box::new(local_service::LocalService::new(true).unwrap());
+    let task = serde_json::json!({"title": "a", "type": "unknown"});
+    let policy = RetryPolicy { retries: 3, delay: Duration::from_secs(60), backoff: 1.0, until: None, timeout: None };
+    let outcome = tokio::time::timeout(Duration::from_secs(5), run_task_with_retries(&mut context, &task, &serde_json::json!({}), true, &policy, "h")).await.unwrap();
+
+    assert_eq!(outcome.status, Status::Failed);
+    assert_eq!(outcome.message, "unknown task type \"unknown\"");
+}
+
+async fn run_task_with_retries(context: &mut Box<dyn Service>, task: &Value, scope: &Value, check_mode: bool, policy: &RetryPolicy, host_title: &str) -> TaskOutcome {
+    let mut delay = policy.delay;
+    let mut attempt = 0;
+
+    loop {
+        let result = run_task_with_timeout(context, task, scope, policy.timeout).await;
+
+        if let Err(err) = context.remove_temp_files().await {
+            warn!("[{}] error removing temporary files: {}", host_title, err);
+        }
+
+        let outcome = match result {
+            Ok(outcome) if outcome.status == Status::Skipped || check_mode => return outcome,
+            Ok(outcome) => match &policy.until {
+                Some(condition) => {
+                    let mut until_scope = scope.clone();
+
+                    until_scope["result"] = outcome.to_value();
+
+                    match expression::is_true(condition, &until_scope) {
+                        Ok(true) => return outcome,
+                        Ok(false) => TaskOutcome { status: Status::Failed, ..outcome.with_message(&*format!("condition \"{}\" not met", condition)) },
+                        Err(err) => return TaskOutcome::failed(&*err.to_string())
+                    }
+                },
+                None => return outcome
             },
-            None => {
-                error!("no task type found");
-                return Err(InfcoError::new("no task type found").into())
+            Err(err) => TaskOutcome::from_error(&*err)
+        };
+
+        if check_mode || attempt >= policy.retries {
+            let message = format!("{} (after {} attempts)", outcome.message, attempt + 1);
+
+            return match attempt {
+                0 => outcome,
+                _ => outcome.with_message(&*message)
+            };
+        }
+
+        attempt += 1;
+        warn!("[{}] attempt {} failed ({}); retrying in {:.1}s", host_title, attempt, outcome.message, delay.as_secs_f64());
+        tokio::time::sleep(delay).await;
+        delay = Duration::try_from_secs_f64(delay.as_secs_f64() * policy.backoff).unwrap_or(Duration::MAX);
+    }
+}
+
+/// Runs a task; once the timeout expires, the task is cancelled, which closes the channel of a remote command or kills a local process.
+async fn run_task_with_timeout(context: &mut Box<dyn Service>, task: &Value, scope: &Value, timeout: Option<Duration>) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return run_task(context, task, scope).await
+    };
+
+    context.set_deadline(Some(Instant::now() + timeout));
+
+    let result = tokio::time::timeout(timeout, run_task(context, task, scope)).await;
+
+    context.set_deadline(None);
+
+    match result {
+        Ok(result) => result,
+        Err(_) => Err(InfcoError::new(&*format!("task timed out after {}s", timeout.as_secs_f64())).into())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct RetryPolicy {
+    retries: u64,
+    delay: Duration,
+    backoff: f64,
+    until: Option<String>,
+    timeout: Option<Duration>,
+}
+
+#[test]
+fn function_get_retry_policy() {
+    assert_eq!(get_retry_policy(&serde_json::json!({}), None).unwrap(), RetryPolicy { retries: 0, delay: Duration::from_secs(1), backoff: 1.0, until: None, timeout: None });
+    assert_eq!(get_retry_policy(&serde_json::json!({"retries": 3, "delay": 0.5, "backoff": 2, "until": "result.stdout == \"up\""}), Some(Duration::from_secs(60))).unwrap(), RetryPolicy { retries: 3, delay: Duration::from_millis(500), backoff: 2.0, until: Some("result.stdout == \"up\"".into()), timeout: Some(Duration::from_secs(60)) });
+    assert_eq!(get_retry_policy(&serde_json::json!({"timeout": 1.5}), Some(Duration::from_secs(60))).unwrap().timeout, Some(Duration::from_millis(1500)));
+    assert!(get_retry_policy(&serde_json::json!({"retries": -1}), None).is_err());
+    assert!(get_retry_policy(&serde_json::json!({"delay": "1s"}), None).is_err());
+    assert!(get_retry_policy(&serde_json::json!({"delay": -1}), None).is_err());
+    assert!(get_retry_policy(&serde_json::json!({"delay": 1e30}), None).is_err());
+    assert!(get_retry_policy(&serde_json::json!({"backoff": 0.5}), None).is_err());
+    assert!(get_retry_policy(&serde_json::json!({"until": "result =="}), None).is_err());
+    assert!(get_retry_policy(&serde_json::json!({"timeout": 0}), None).is_err());
+    assert!(get_retry_policy(&serde_json::json!({"timeout": 1e30}), None).is_err());
+    assert!(parse_timeout("inf".parse().ok()).is_err());
+}
+
+/// Reads the retry policy of a task; `retries` is the number of additional attempts, `delay` the time in seconds before the first retry and `backoff` the factor by which the delay grows with each retry.
+///
+/// The `timeout` of the task in seconds replaces the default timeout.
+fn get_retry_policy(task: &Value, default_timeout: Option<Duration>) -> Result<RetryPolicy, Box<dyn std::error::Error>> {
+    let retries = match &task["retries"] {
+        Value::Null => 0,
+        value => value.as_u64().ok_or(InfcoError::new("retries must be a non-negative integer"))?
+    };
+    let delay = match &task["delay"] {
+        Value::Null => Duration::from_secs(1),
+        value => value.as_f64().and_then(|delay| Duration::try_from_secs_f64(delay).ok()).ok_or(InfcoError::new("delay must be a non-negative number of seconds"))?
+    };
+    let backoff = match &task["backoff"] {
+        Value::Null => 1.0,
+        value => value.as_f64().filter(|backoff| *backoff >= 1.0).ok_or(InfcoError::new("backoff must be a number of at least 1"))?
+    };
+    let until = match &task["until"] {
+        Value::Null => None,
+        Value::String(condition) => {
+            expression::parse_expression(condition)?;
+            Some(condition.clone())
+        },
+        _ => return Err(InfcoError::new("until must be a string").into())
+    };
+
+    let timeout = match &task["timeout"] {
+        Value::Null => default_timeout,
+        value => Some(parse_timeout(value.as_f64())?)
+    };
+
+    Ok(RetryPolicy { retries, delay, backoff, until, timeout })
+}
+
+fn parse_timeout(seconds: Option<f64>) -> Result<Duration, InfcoError> {
+    seconds.filter(|timeout| *timeout > 0.0).and_then(|timeout| Duration::try_from_secs_f64(timeout).ok()).ok_or(InfcoError::new("timeout must be a positive number of seconds"))
+}
+
+fn record_task(task: &Value, scope: &Value, outcome: TaskOutcome, check_mode: bool, host_outcome: &mut HostOutcome) -> Status {
+    let status = outcome.status;
+    let title = task["title"].as_str().unwrap_or("");
+
+    match (status, check_mode) {
+        (Status::Changed, true) => info!("[{}] changed (check mode)", host_outcome.title),
+        (Status::Failed, _) => error!("[{}] failed: {}", host_outcome.title, outcome.message),
+        (Status::Ignored, _) => warn!("[{}] failed (ignored): {}", host_outcome.title, outcome.message),
+        (status, _) => info!("[{}] {}", host_outcome.title, status)
+    }
+
+    host_outcome.tasks.push(TaskResult {
+        title: match scope.get("item") {
+            Some(item) => format!("{} ({})", title, item),
+            None => title.into()
+        },
+        task_type: task["type"].as_str().unwrap_or("").into(),
+        outcome
+    });
+
+    status
+}
+
+#[test]
+fn function_get_loop_items() {
+    let scope = serde_json::json!({"vars": {"dirs": ["a", "b"], "name": "x"}});
+
+    assert_eq!(get_loop_items(&serde_json::json!({}), &scope).unwrap(), None);
+    assert_eq!(get_loop_items(&serde_json::json!({"loop": [1, 2]}), &scope).unwrap(), Some(vec![Value::from(1), Value::from(2)]));
+    assert_eq!(get_loop_items(&serde_json::json!({"loop": "vars.dirs"}), &scope).unwrap(), Some(vec![Value::from("a"), Value::from("b")]));
+    assert_eq!(get_loop_items(&serde_json::json!({"loop": "{{ vars.dirs }}"}), &scope).unwrap(), Some(vec![Value::from("a"), Value::from("b")]));
+    assert!(get_loop_items(&serde_json::json!({"loop": "vars.name"}), &scope).is_err());
+    assert!(get_loop_items(&serde_json::json!({"loop": "vars.missing"}), &scope).is_err());
+}
+
+/// Returns the items of the `loop` of a task; the loop is either an array or the path of a variable containing an array.
+fn get_loop_items(task: &Value, scope: &Value) -> Result<Option<Vec<Value>>, InfcoError> {
+    match &task["loop"] {
+        Value::Null => Ok(None),
+        Value::Array(items) => Ok(Some(items.clone())),
+        Value::String(path) => {
+            let path = path.trim().trim_start_matches("{{").trim_end_matches("}}").trim();
+
+            match renderer::lookup(scope, path) {
+                Some(Value::Array(items)) => Ok(Some(items.clone())),
+                Some(_) => Err(InfcoError::new(&*format!("loop variable \"{}\" is not a list", path))),
+                None => Err(InfcoError::new(&*format!("loop variable \"{}\" not found", path)))
+            }
+        },
+        _ => Err(InfcoError::new("loop must be a list or the name of a variable"))
+    }
+}
+
+#[test]
+fn function_get_notifications() {
+    assert_eq!(get_notifications(&serde_json::json!({"notify": "restart nginx"})), vec!["restart nginx"]);
+    assert_eq!(get_notifications(&serde_json::json!({"notify": ["a", "b"]})), vec!["a", "b"]);
+    assert!(get_notifications(&serde_json::json!({})).is_empty());
+}
+
+/// Returns the titles of the handlers notified by a task; `notify` is either a single title or an array of titles.
+fn get_notifications(task: &Value) -> Vec<&str> {
+    match &task["notify"] {
+        Value::String(title) => vec![&**title],
+        Value::Array(titles) => titles.iter().filter_map(|title| title.as_str()).collect(),
+        _ => Vec::new()
+    }
+}
+
+/// Checks that the conditions of the tasks and handlers are well-formed and that every handler notified by a task is defined.
+fn check_tasks(tasks: &[Value], handlers: &[Value]) -> Result<(), Box<dyn std::error::Error>> {
+    for task in tasks.iter().chain(handlers) {
+        if let Some(condition) = get_condition(task)? {
+            expression::parse_expression(condition).map_err(|err| InfcoError::new(&*format!("task {}: {}", task["title"], err)))?;
+        }
+
+        get_retry_policy(task, None).map_err(|err| InfcoError::new(&*format!("task {}: {}", task["title"], err)))?;
+
+        match &task["register"] {
+            Value::Null => {},
+            Value::String(name) if !["vars", "item", "result"].contains(&&**name) => {},
+            _ => return Err(InfcoError::new(&*format!("task {}: register must be a variable name other than vars, item and result", task["title"])).into())
+        }
+
+        match &task["tags"] {
+            Value::Null => {},
+            Value::Array(tags) if tags.iter().all(Value::is_string) => {},
+            _ => return Err(InfcoError::new(&*format!("task {}: tags must be an array of strings", task["title"])).into())
+        }
+    }
+
+    for task in tasks {
+        for title in get_notifications(task) {
+            if !handlers.iter().any(|handler| handler["title"].as_str() == Some(title)) {
+                return Err(InfcoError::new(&*format!("task {} notifies unknown handler \"{}\"", task["title"], title)).into());
             }
         }
     }
@@ -81,6 +666,143 @@ async fn process_tasks_for_host(tasks: &Vec<Value>, host: &Value) -> Result<(),
     Ok(())
 }
 
+fn get_values(matches: &clap::ArgMatches, name: &str) -> Vec<String> {
+    matches.values_of(name).into_iter().flatten().map(String::from).collect()
+}
+
+#[test]
+fn function_get_default_state_path() {
+    assert_eq!(get_default_state_path("deploy/site.json"), "deploy/site.state.json");
+    assert_eq!(get_default_state_path("tasks"), "tasks.state.json");
+}
+
+/// Returns the path of the state file next to the tasks file, which is written unless another path is given or writing it is turned off.
+fn get_default_state_path(tasks_path: &str) -> String {
+    std::path::Path::new(tasks_path).with_extension("state.json").to_string_lossy().to_string()
+}
+
+fn create_context(host: &Value, check_mode: bool) -> Result<Box<dyn Service>, Box<dyn std::error::Error>> {
+    match host["context"]["type"].as_str() {
+        Some("ssh") => {
+            let host_name = host["context"]["config"]["host"].as_str().ok_or(InfcoError::new("no host specified"))?;
+            let user_name = host["context"]["config"]["username"].as_str().ok_or(InfcoError::new("no user specified"))?;
+            let hash =host["context"]["config"]["serverPublicKeyHash"].as_str().ok_or(InfcoError::new("no hash specified"))?;
+
+            Ok(Box::new(ssh_service::SshService::new(host_name.into(), user_name.into(), hash.into(), check_mode)?))
+        },
+        Some("local") => Ok(Box::new(local_service::LocalService::new(check_mode)?)),
+        Some(name) => Err(InfcoError::new(&*format!("unknown context type \"{}\"", name)).into()),
+        None => Err(InfcoError::new("no context type found").into())
+    }
+}
+
+fn get_condition(task: &Value) -> Result<Option<&str>, InfcoError> {
+    match &task["when"] {
+        Value::Null => Ok(None),
+        Value::String(condition) => Ok(Some(condition)),
+        _ => Err(InfcoError::new(&*format!("condition of task {} must be a string", task["title"])))
+    }
+}
+
+#[test]
+fn function_get_scope() {
+    let mut registered = serde_json::Map::new();
+
+    registered.insert("kernel".into(), serde_json::json!("registered"));
+
+    let scope = get_scope(&serde_json::json!({"vars": {"role": "db"}}), &serde_json::json!({"os": {"family": "debian"}, "kernel": {"release": "5.10"}}), &registered);
+
+    assert_eq!(scope, serde_json::json!({"os": {"family": "debian"}, "kernel": "registered", "vars": {"role": "db"}}));
+}
+
+#[test]
+fn function_apply_role_defaults() {
+    let scope = serde_json::json!({"kernel": "Linux", "vars": {"name": "web"}});
+
+    assert_eq!(apply_role_defaults(&scope, &serde_json::json!({"title": "a", "roleDefaults": {"port": 80, "name": "default"}}))["vars"], serde_json::json!({"port": 80, "name": "web"}));
+    assert_eq!(apply_role_defaults(&serde_json::json!({"vars": null}), &serde_json::json!({"title": "a", "roleDefaults": {"port": 80}}))["vars"], serde_json::json!({"port": 80}));
+    assert_eq!(apply_role_defaults(&scope, &serde_json::json!({"title": "a"})), scope);
+}
+
+/// Adds the default variables of the role of a task, if any, to the variables of the host for that task; the variables of the host take precedence.
+fn apply_role_defaults(scope: &Value, task: &Value) -> Value {
+    let mut scope = scope.clone();
+
+    if let Some(defaults) = task["roleDefaults"].as_object() {
+        let mut vars = defaults.clone();
+
+        vars.extend(scope["vars"].as_object().cloned().unwrap_or_default());
+        scope["vars"] = Value::Object(vars);
+    }
+
+    scope
+}
+
+/// Builds the variables available to the conditions and configs of the tasks of a host.
+///
+/// The facts of the host are available under their names, registered values take precedence over facts, and the variables of the host are available as `vars`.
+fn get_scope(host: &Value, facts: &Value, registered: &serde_json::Map<String, Value>) -> Value {
+    let mut scope = facts.as_object().cloned().unwrap_or_default();
+
+    scope.extend(registered.clone());
+    scope.insert("vars".into(), host["vars"].clone());
+    Value::Object(scope)
+}
+
+/// Builds the variables of a template; the variables of the host are merged with the facts, the registered values and the current loop item.
+fn get_template_vars(scope: &Value) -> Value {
+    let mut vars = scope["vars"].as_object().cloned().unwrap_or_default();
+
+    for (name, value) in scope.as_object().into_iter().flatten().filter(|(name, _)| *name != "vars") {
+        vars.insert(name.clone(), value.clone());
+    }
+
+    Value::Object(vars)
+}
+
+#[tokio::test]
+async fn function_run_task() {
+    let mut context: Box<dyn Service> = Box::new(local_service::LocalService::new(false).unwrap());
+    let literal = run_task(&mut context, &serde_json::json!({"title": "a", "type": "command", "config": {"command": "echo '{{.Names}}'"}}), &serde_json::json!({})).await.unwrap();
+    let looped = run_task(&mut context, &serde_json::json!({"title": "a", "type": "command", "loop": [1], "config": {"command": "echo {{ item }}"}}), &serde_json::json!({"item": 1})).await.unwrap();
+
+    assert_eq!(literal.stdout, "{{.Names}}\n");
+    assert_eq!(looped.stdout, "1\n");
+}
+
+/// Runs a task if its condition holds.
+///
+/// For a task with a `loop`, the strings of the config are rendered as templates with the variables of the scope, which include the `item`; literal template syntax has to be escaped with `{% raw %}`. The configs of other tasks are used as they are.
+async fn run_task(context: &mut Box<dyn Service>, task: &Value, scope: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    if let Some(condition) = get_condition(task)? {
+        if !expression::is_true(condition, scope)? {
+            return Ok(TaskOutcome::skipped(&*format!("condition \"{}\" is false", condition)));
+        }
+    }
+
+    let config = &match task["loop"] {
+        Value::Null => task["config"].clone(),
+        _ => renderer::render_value(&task["config"], scope)?
+    };
+
+    match task["type"].as_str() {
+        Some("command") => command::run(context, config).await,
+        Some("fileTransfer") => file_transfer::run(context, config).await,
+        Some("template") => template::run(context, config, &get_template_vars(scope)).await,
+        Some("lineInFile") => line_in_file::run(context, config).await,
+        Some("blockInFile") => block_in_file::run(context, config).await,
+        Some("package") => package::run(context, config).await,
+        Some("systemd") => systemd::run(context, config).await,
+        Some("dockerContainer") => docker_container::run(context, config).await,
+        Some("user") => user::run(context, config).await,
+        Some("group") => group::run(context, config).await,
+        Some("unarchive") => unarchive::run(context, config).await,
+        Some("script") => script::run(context, config).await,
+        Some(name) => Err(InfcoError::new(&*format!("unknown task type \"{}\"", name)).into()),
+        None => Err(InfcoError::new("no task type found").into())
+    }
+}
+
 #[test]
 fn function_vecs_have_common_entries() {
     assert_eq!(vecs_have_common_entries(&vec!["test1"], &vec!["test2"]), false);
@@ -120,6 +842,14 @@ fn get_matches() -> clap::ArgMatches<'static> {
                 .takes_value(true)
                 .required(true)
                 .help("username")))
+        .subcommand(SubCommand::with_name("facts")
+            .about("print the facts of the hosts as JSON")
+            .arg(Arg::with_name("hosts")
+                .short("h")
+                .takes_value(true)
+                .required(true)
+                .help("host file"))
+            .arg(limit_arg()))
         .subcommand(SubCommand::with_name("process")
             .about("process a combination of task and host files")
             .arg(Arg::with_name("hosts")
@@ -131,6 +861,75 @@ fn get_matches() -> clap::ArgMatches<'static> {
                 .short("t")
                 .takes_value(true)
                 .required(true)
-                .help("task file")))
+                .help("task file"))
+            .arg(Arg::with_name("check")
+                .long("check")
+                .help("report the changes without applying them"))
+            .arg(limit_arg())
+            .arg(Arg::with_name("tags")
+                .long("tags")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+                .help("run only the tasks with one of the given comma-separated tags"))
+            .arg(Arg::with_name("skip-tags")
+                .long("skip-tags")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+                .help("skip the tasks with one of the given comma-separated tags"))
+            .arg(Arg::with_name("forks")
+                .short("f")
+                .long("forks")
+                .takes_value(true)
+                .default_value("5")
+                .help("maximum number of hosts processed concurrently"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .help("default timeout of a task in seconds"))
+            .arg(Arg::with_name("state")
+                .long("state")
+                .takes_value(true)
+                .help("file to which the progress of the run is written [default: <tasks>.state.json next to the tasks file]"))
+            .arg(Arg::with_name("no-state")
+                .long("no-state")
+                .conflicts_with_all(&["state", "resume"])
+                .help("do not write the progress of the run to a state file"))
+            .arg(Arg::with_name("resume")
+                .long("resume")
+                .takes_value(true)
+                .conflicts_with("state")
+                .help("resume the run recorded in the given state file, skipping completed hosts and tasks; the tags, skipped tags and limit must be the same as in the recorded run"))
+            .arg(Arg::with_name("keep-going")
+                .long("keep-going")
+                .help("continue processing the other hosts after a host failed (default)"))
+            .arg(Arg::with_name("fail-fast")
+                .long("fail-fast")
+                .conflicts_with_all(&["keep-going", "max-fail-percentage"])
+                .help("do not process further hosts after a host failed (same as --max-fail-percentage 0)"))
+            .arg(Arg::with_name("max-fail-percentage")
+                .long("max-fail-percentage")
+                .takes_value(true)
+                .conflicts_with("keep-going")
+                .help("percentage of failed hosts above which no further hosts are processed (default: 100)"))
+            .arg(Arg::with_name("report")
+                .long("report")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("write a report of the run given as json:<path> or junit:<path>")))
         .get_matches()
 }
+
+fn limit_arg() -> clap::Arg<'static, 'static> {
+    clap::Arg::with_name("limit")
+        .long("limit")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .use_delimiter(true)
+        .help("process only the hosts whose title matches one of the given comma-separated glob patterns")
+}
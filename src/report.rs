@@ -0,0 +1,205 @@
+use crate::error::InfcoError;
+use crate::task::outcome::{HostOutcome, Status, TaskOutcome, TaskResult};
+use serde_json::{json, Value};
+use tokio::fs::write;
+
+#[derive(Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Junit,
+}
+
+/// A report of a run to be written to a file.
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub format: Format,
+    pub path: String,
+}
+
+#[test]
+fn function_parse() {
+    assert_eq!(Report::parse("json:out/report.json").unwrap(), Report { format: Format::Json, path: "out/report.json".into() });
+    assert_eq!(Report::parse("junit:C:/report.xml").unwrap(), Report { format: Format::Junit, path: "C:/report.xml".into() });
+    assert!(Report::parse("yaml:report.yaml").is_err());
+    assert!(Report::parse("json:").is_err());
+    assert!(Report::parse("report.json").is_err());
+}
+
+impl Report {
+    /// Parses a report given as `<format>:<path>`.
+    pub fn parse(spec: &str) -> Result<Self, InfcoError> {
+        let (format, path) = match spec.find(':') {
+            Some(index) => (&spec[..index], &spec[index + 1..]),
+            None => return Err(InfcoError::new(&*format!("report \"{}\" must be given as <format>:<path>", spec)))
+        };
+        let format = match format {
+            "json" => Format::Json,
+            "junit" => Format::Junit,
+            format => return Err(InfcoError::new(&*format!("unknown report format \"{}\"", format)))
+        };
+
+        if path.is_empty() {
+            return Err(InfcoError::new(&*format!("no path given for report \"{}\"", spec)));
+        }
+
+        Ok(Report { format, path: path.into() })
+    }
+
+    pub async fn write(&self, host_outcomes: &[HostOutcome]) -> Result<(), Box<dyn std::error::Error>> {
+        let content = match self.format {
+            Format::Json => serde_json::to_string_pretty(&render_json(host_outcomes))?,
+            Format::Junit => render_junit(host_outcomes),
+        };
+
+        write(&self.path, content).await?;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn function_render_json() {
+    let mut host = HostOutcome::new("web1");
+
+    host.tasks.push(TaskResult { title: "a".into(), task_type: "command".into(), outcome: TaskOutcome::failed("boom") });
+
+    let report = render_json(&[host]);
+
+    assert_eq!(report["hosts"][0]["title"], "web1");
+    assert_eq!(report["hosts"][0]["failed"], true);
+    assert_eq!(report["hosts"][0]["tasks"][0]["type"], "command");
+    assert_eq!(report["hosts"][0]["tasks"][0]["status"], "failed");
+    assert_eq!(report["hosts"][0]["tasks"][0]["error"], "boom");
+    assert_eq!(render_json(&[HostOutcome::not_processed("web2")])["hosts"][0]["processed"], false);
+}
+
+/// Renders the outcomes as a JSON document; durations are given in seconds and hosts left out after too many hosts failed are marked as not processed.
+pub fn render_json(host_outcomes: &[HostOutcome]) -> Value {
+    json!({
+        "hosts": host_outcomes.iter().map(|host_outcome| json!({
+            "title": host_outcome.title,
+            "processed": host_outcome.processed,
+            "failed": host_outcome.is_failed(),
+            "error": host_outcome.error,
+            "tasks": host_outcome.tasks.iter().map(|task| json!({
+                "title": task.title,
+                "type": task.task_type,
+                "status": task.outcome.status.to_string(),
+                "duration": task.outcome.duration.as_secs_f64(),
+                "stdout": task.outcome.stdout,
+                "stderr": task.outcome.stderr,
+                "message": task.outcome.message,
+                "exitCode": task.outcome.exit_code,
+                "error": error_text(&task.outcome),
+            })).collect::<Vec<Value>>()
+        })).collect::<Vec<Value>>()
+    })
+}
+
+fn error_text(outcome: &TaskOutcome) -> Option<&str> {
+    match outcome.status {
+        Status::Failed | Status::Ignored => Some(&*outcome.message),
+        _ => None
+    }
+}
+
+#[test]
+fn function_render_junit() {
+    let mut host = HostOutcome::new("web<1>");
+
+    host.tasks.push(TaskResult { title: "a".into(), task_type: "command".into(), outcome: TaskOutcome::changed() });
+    host.tasks.push(TaskResult { title: "b".into(), task_type: "command".into(), outcome: TaskOutcome::skipped("exists") });
+    host.tasks.push(TaskResult { title: "c".into(), task_type: "script".into(), outcome: TaskOutcome::failed("exit \"3\"") });
+
+    let report = render_junit(&[host, HostOutcome { error: Some("no host specified".into()), ..HostOutcome::new("db") }, HostOutcome::not_processed("cache")]);
+
+    assert!(report.contains("<testsuites tests=\"5\" failures=\"1\" errors=\"1\" skipped=\"2\""));
+    assert!(report.contains("<testsuite name=\"web&lt;1&gt;\" tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"1\""));
+    assert!(report.contains("<testcase name=\"c\" classname=\"web&lt;1&gt;.script\""));
+    assert!(report.contains("<failure message=\"exit &quot;3&quot;\"/>"));
+    assert!(report.contains("<skipped message=\"exists\"/>"));
+    assert!(report.contains("<error message=\"no host specified\"/>"));
+    assert!(report.contains("<testsuite name=\"cache\" tests=\"1\" failures=\"0\" errors=\"0\" skipped=\"1\""));
+    assert!(report.contains("<skipped message=\"not processed\"/>"));
+}
+
+/// Renders the outcomes as JUnit XML with a test suite per host and a test case per task.
+///
+/// A host that could not be processed at all is reported as a single test case with an error; a host left out after too many hosts failed as a single skipped test case.
+pub fn render_junit(host_outcomes: &[HostOutcome]) -> String {
+    let mut suites = String::new();
+    let (mut total_tests, mut total_failures, mut total_errors, mut total_skipped, mut total_time) = (0, 0, 0, 0, 0.0);
+
+    for host_outcome in host_outcomes {
+        let title = escape(&host_outcome.title);
+        let failures = host_outcome.count(Status::Failed);
+        let not_processed = if host_outcome.processed { 0 } else { 1 };
+        let skipped = host_outcome.count(Status::Skipped) + not_processed;
+        let errors = if host_outcome.error.is_some() { 1 } else { 0 };
+        let tests = host_outcome.tasks.len() + errors + not_processed;
+        let time: f64 = host_outcome.tasks.iter().map(|task| task.outcome.duration.as_secs_f64()).sum();
+
+        suites += &*format!("  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n", title, tests, failures, errors, skipped, time);
+
+        if let Some(error) = &host_outcome.error {
+            suites += &*format!("    <testcase name=\"context\" classname=\"{}\" time=\"0.000\">\n      <error message=\"{}\"/>\n    </testcase>\n", title, escape(error));
+        }
+
+        if !host_outcome.processed {
+            suites += &*format!("    <testcase name=\"context\" classname=\"{}\" time=\"0.000\">\n      <skipped message=\"not processed\"/>\n    </testcase>\n", title);
+        }
+
+        for task in &host_outcome.tasks {
+            suites += &*render_junit_test_case(&title, task);
+        }
+
+        suites += "  </testsuite>\n";
+        total_tests += tests;
+        total_failures += failures;
+        total_errors += errors;
+        total_skipped += skipped;
+        total_time += time;
+    }
+
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n{}</testsuites>\n", total_tests, total_failures, total_errors, total_skipped, total_time, suites)
+}
+
+fn render_junit_test_case(host_title: &str, task: &TaskResult) -> String {
+    let mut test_case = format!("    <testcase name=\"{}\" classname=\"{}.{}\" time=\"{:.3}\">\n", escape(&task.title), host_title, escape(&task.task_type), task.outcome.duration.as_secs_f64());
+
+    match task.outcome.status {
+        Status::Failed => test_case += &*format!("      <failure message=\"{}\"/>\n", escape(&task.outcome.message)),
+        Status::Skipped => test_case += &*format!("      <skipped message=\"{}\"/>\n", escape(&task.outcome.message)),
+        _ => {}
+    }
+
+    if !task.outcome.stdout.is_empty() {
+        test_case += &*format!("      <system-out>{}</system-out>\n", escape(&task.outcome.stdout));
+    }
+
+    if !task.outcome.stderr.is_empty() {
+        test_case += &*format!("      <system-err>{}</system-err>\n", escape(&task.outcome.stderr));
+    }
+
+    test_case + "    </testcase>\n"
+}
+
+#[test]
+fn function_escape() {
+    assert_eq!(escape("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&apos;");
+    assert_eq!(escape("\x1b[31mred\x1b[0m\tok\r\n\u{0}\u{fffe}"), "\u{fffd}[31mred\u{fffd}[0m\tok\r\n\u{fffd}\u{fffd}");
+}
+
+/// Escapes text for use in XML content and attribute values; characters not allowed in XML 1.0, such as the escape character of terminal colours, are replaced by U+FFFD.
+fn escape(text: &str) -> String {
+    text.chars().map(|c| match c {
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '&' => "&amp;".to_string(),
+        '"' => "&quot;".to_string(),
+        '\'' => "&apos;".to_string(),
+        '\t' | '\n' | '\r' => c.to_string(),
+        '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => '\u{fffd}'.to_string(),
+        c => c.to_string()
+    }).collect()
+}
@@ -0,0 +1,89 @@
+use serde_json::Value;
+
+/// The tasks selected by their tags; a task is run if it has one of `tags`, or `tags` is empty, and none of `skip_tags`.
+#[derive(Debug, Default)]
+pub struct TaskSelection {
+    pub tags: Vec<String>,
+    pub skip_tags: Vec<String>,
+}
+
+#[test]
+fn function_includes() {
+    let selection = TaskSelection { tags: vec!["nginx".into(), "ssl".into()], skip_tags: vec!["slow".into()] };
+    let task = |tags: Value| serde_json::json!({"title": "a", "tags": tags});
+
+    assert!(selection.includes(&task(serde_json::json!(["nginx"]))));
+    assert!(selection.includes(&task(serde_json::json!(["base", "ssl"]))));
+    assert!(!selection.includes(&task(serde_json::json!(["ssl", "slow"]))));
+    assert!(!selection.includes(&task(serde_json::json!(["base"]))));
+    assert!(!selection.includes(&serde_json::json!({"title": "a"})));
+    assert!(TaskSelection::default().includes(&serde_json::json!({"title": "a"})));
+    assert!(!TaskSelection { tags: Vec::new(), skip_tags: vec!["slow".into()] }.includes(&task(serde_json::json!(["slow"]))));
+}
+
+#[test]
+fn function_to_value() {
+    let selection = TaskSelection { tags: vec!["ssl".into(), "nginx".into()], skip_tags: Vec::new() };
+
+    assert_eq!(selection.to_value(&["web*".into()]), serde_json::json!({"tags": ["nginx", "ssl"], "skipTags": [], "limit": ["web*"]}));
+    assert_eq!(selection.to_value(&[]), TaskSelection { tags: vec!["nginx".into(), "ssl".into(), "ssl".into()], skip_tags: Vec::new() }.to_value(&[]));
+    assert_ne!(selection.to_value(&[]), TaskSelection::default().to_value(&[]));
+}
+
+impl TaskSelection {
+    pub fn includes(&self, task: &Value) -> bool {
+        let task_tags: Vec<&str> = task["tags"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+        let has_any = |tags: &[String]| tags.iter().any(|tag| task_tags.contains(&&**tag));
+
+        (self.tags.is_empty() || has_any(&self.tags)) && !has_any(&self.skip_tags)
+    }
+
+    /// Describes the selection together with the limit of hosts, independently of the order in which tags and patterns were given; the description is recorded in the state file.
+    pub fn to_value(&self, limit: &[String]) -> Value {
+        let sorted = |values: &[String]| {
+            let mut values = values.to_vec();
+
+            values.sort();
+            values.dedup();
+            values
+        };
+
+        serde_json::json!({"tags": sorted(&self.tags), "skipTags": sorted(&self.skip_tags), "limit": sorted(limit)})
+    }
+}
+
+#[test]
+fn function_matches_limit() {
+    assert!(matches_limit("web1", &[]));
+    assert!(matches_limit("web1", &["web*".into()]));
+    assert!(matches_limit("db1", &["web*".into(), "db?".into()]));
+    assert!(!matches_limit("db12", &["web*".into(), "db?".into()]));
+    assert!(matches_limit("eu-web-1", &["*web*".into()]));
+    assert!(!matches_limit("web1", &["web".into()]));
+}
+
+/// Checks whether the title of a host matches one of the glob patterns of the limit; an empty limit matches all hosts.
+pub fn matches_limit(title: &str, patterns: &[String]) -> bool {
+    patterns.is_empty() || patterns.iter().any(|pattern| matches_glob(&pattern.chars().collect::<Vec<char>>(), &title.chars().collect::<Vec<char>>()))
+}
+
+#[test]
+fn function_matches_glob() {
+    let glob = |pattern: &str, text: &str| matches_glob(&pattern.chars().collect::<Vec<char>>(), &text.chars().collect::<Vec<char>>());
+
+    assert!(glob("*", ""));
+    assert!(glob("a*b*c", "aXXbYc"));
+    assert!(!glob("a*b*c", "aXXbY"));
+    assert!(glob("?b", "ab"));
+    assert!(!glob("?b", "b"));
+}
+
+/// Matches a text against a glob pattern, in which `*` matches any sequence of characters and `?` a single character.
+fn matches_glob(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skipped| matches_glob(rest, &text[skipped..])),
+        Some(('?', rest)) => !text.is_empty() && matches_glob(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && matches_glob(rest, &text[1..])
+    }
+}
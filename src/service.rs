@@ -1,8 +1,87 @@
 use async_trait::async_trait;
+use std::fmt::{Display, Formatter};
+use std::time::Instant;
 
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>
+}
+
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32
+}
+
+impl CommandOutput {
+    /// Turns the output of a command that exited with a non-zero status into an error.
+    pub fn into_result(self) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        match self.exit_code {
+            0 => Ok(self),
+            _ => Err(CommandError { output: self }.into())
+        }
+    }
+}
+
+/// The error of a command that exited with a non-zero status; the output is kept, so that it can be reported.
+#[derive(Debug)]
+pub struct CommandError {
+    pub output: CommandOutput
+}
+
+impl std::error::Error for CommandError {
+
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.output.stderr.trim() {
+            "" => write!(f, "command failed (exit status: {})", self.output.exit_code),
+            stderr => write!(f, "command failed (exit status: {}) {}", self.output.exit_code, stderr)
+        }
+    }
+}
+
+/// Checks whether an error of a service means that a file or directory does not exist.
+pub fn is_not_found(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<std::io::Error>().is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound)
+}
+
+pub struct DirEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub mode: u32
+}
+
+/// A context in which tasks are executed.
+///
+/// In check mode, implementations refuse all mutating calls; read-only probes have to use `query` instead of `run`.
 #[async_trait]
 pub trait Service {
-    async fn run(&mut self, command: String) -> Result<String, Box<dyn std::error::Error>>;
+    fn check_mode(&self) -> bool;
+    /// Sets the point in time after which running commands are cancelled; `None` removes the deadline.
+    fn set_deadline(&mut self, deadline: Option<Instant>);
+    async fn query(&mut self, command: String) -> Result<CommandOutput, Box<dyn std::error::Error>>;
+    async fn run(&mut self, command: String) -> Result<CommandOutput, Box<dyn std::error::Error>>;
     async fn file_read(&mut self, path: String) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
     async fn file_write(&mut self, path: String, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>>;
+    async fn dir_read(&mut self, path: String) -> Result<Vec<DirEntry>, Box<dyn std::error::Error>>;
+    async fn dir_create(&mut self, path: String) -> Result<(), Box<dyn std::error::Error>>;
+    async fn dir_remove(&mut self, path: String) -> Result<(), Box<dyn std::error::Error>>;
+    async fn file_remove(&mut self, path: String) -> Result<(), Box<dyn std::error::Error>>;
+    async fn file_set_mode(&mut self, path: String, mode: u32) -> Result<(), Box<dyn std::error::Error>>;
+    async fn socket_request(&mut self, socket: String, request: Request) -> Result<Response, Box<dyn std::error::Error>>;
+    /// Records a temporary file, which `remove_temp_files` removes unless it has been removed with `file_remove` before.
+    fn add_temp_file(&mut self, path: String);
+    /// Removes the temporary files left behind, e.g. by a task that was cancelled before it could clean up.
+    async fn remove_temp_files(&mut self) -> Result<(), Box<dyn std::error::Error>>;
 }
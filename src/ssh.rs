@@ -2,6 +2,7 @@ mod error;
 mod channel;
 mod wrapper;
 pub mod sftp_file;
+pub mod sftp_dir;
 pub mod sftp_session;
 pub mod ssh_service;
 pub mod session;
@@ -3,6 +3,7 @@ use std::ffi::{CString};
 use std::error::Error;
 use super::error::SshError;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 pub struct Channel {
     pub ptr: Arc<Mutex<*mut libc::c_void>>,
@@ -72,14 +73,38 @@ impl Channel {
         }
     }
 
-    pub fn read(&mut self) -> Result<Vec<u8>, Box<dyn Error>> {
+    /// Sends a signal, named without the `SIG` prefix, to the remote process; servers are free to ignore it.
+    pub fn send_signal(&mut self, signal: &str) -> Result<(), Box<dyn Error>> {
+        match unsafe { wrapper::ssh_channel_request_send_signal(*self.ptr.lock().unwrap(), CString::new(signal)?.as_ptr()) } {
+            wrapper::ssh_result::SshOk => Ok(()),
+            _ => Err(SshError::new("error sending signal").into())
+        }
+    }
+
+    /// Reads the data sent to stdout until the end of the stream; once the deadline, if any, has passed, the remote process is killed and an error is returned.
+    pub fn read(&mut self, deadline: Option<Instant>) -> Result<Vec<u8>, Box<dyn Error>> {
         let buffer_size: usize = 1024;
         let mut dst = Vec::<u8>::with_capacity(buffer_size);
         let pdst = dst.as_mut_ptr() as *mut wrapper::libc::c_void;
         let mut out = Vec::<u8>::new();
 
         while unsafe { wrapper::ssh_channel_is_eof(*self.ptr.lock().unwrap()) } == 0 {
-            let bytes_read = unsafe { wrapper::ssh_channel_read(*self.ptr.lock().unwrap(), pdst, buffer_size as u32, 0) };
+            let bytes_read = match deadline {
+                Some(deadline) => {
+                    let remaining = match deadline.checked_duration_since(Instant::now()) {
+                        Some(remaining) => remaining,
+                        None => {
+                            self.send_signal("KILL").ok();
+
+                            return Err(SshError::new("command timed out").into())
+                        }
+                    };
+                    let timeout_ms = remaining.as_millis().clamp(1, 1000) as libc::c_int;
+
+                    unsafe { wrapper::ssh_channel_read_timeout(*self.ptr.lock().unwrap(), pdst, buffer_size as u32, 0, timeout_ms) }
+                },
+                None => unsafe { wrapper::ssh_channel_read(*self.ptr.lock().unwrap(), pdst, buffer_size as u32, 0) }
+            };
 
             if bytes_read > 0 {
                 unsafe {dst.set_len(bytes_read as usize);}
@@ -92,6 +117,32 @@ impl Channel {
 
         Ok(out)
     }
+
+    /// Reads the data sent to stderr; has to be called after `read` has reached the end of the stdout stream.
+    pub fn read_stderr(&mut self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let buffer_size: usize = 1024;
+        let mut dst = Vec::<u8>::with_capacity(buffer_size);
+        let pdst = dst.as_mut_ptr() as *mut wrapper::libc::c_void;
+        let mut out = Vec::<u8>::new();
+
+        loop {
+            let bytes_read = unsafe { wrapper::ssh_channel_read(*self.ptr.lock().unwrap(), pdst, buffer_size as u32, 1) };
+
+            if bytes_read > 0 {
+                unsafe {dst.set_len(bytes_read as usize);}
+                out.reserve(bytes_read as usize);
+                out.append(&mut dst);
+            } else if bytes_read < 0 {
+                return Err(SshError::new("error reading from channel").into())
+            } else {
+                return Ok(out)
+            }
+        }
+    }
+
+    pub fn get_exit_status(&mut self) -> i32 {
+        unsafe { wrapper::ssh_channel_get_exit_status(*self.ptr.lock().unwrap()) }
+    }
 }
 
 impl Drop for Channel {
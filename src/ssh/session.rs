@@ -6,13 +6,19 @@ use std::ffi::{CString, CStr};
 use std::error::Error;
 use std::sync::{Arc, Mutex};
 use std::string::{String};
+use std::time::Instant;
+use std::sync::mpsc::RecvTimeoutError;
 use tokio::net::{TcpListener};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use hyper;
 use super::sftp_session::SftpSession;
+use crate::service::{Request, Response, DirEntry, CommandOutput};
 
 pub struct Session {
     ptr: Arc<Mutex<*mut libc::c_void>>,
+    hash: String,
+    deadline: Option<Instant>,
+    disconnected: bool,
 }
 
 pub enum RequestType {
@@ -20,10 +26,23 @@ pub enum RequestType {
     HostPort (String, u16)
 }
 
-pub struct Request {
-    pub method: String,
-    pub path: String,
-    pub body: Option<Vec<u8>>
+#[test]
+#[ignore = "needs an SSH server given by INFCO_TEST_SSH_HOST, INFCO_TEST_SSH_USER and INFCO_TEST_SSH_HASH"]
+fn function_run_command() {
+    let var = |name: &str| std::env::var(name).unwrap_or_else(|_| panic!("{} is not set", name));
+    let mut session = Session::new_with_host_user_hash(&var("INFCO_TEST_SSH_HOST"), &var("INFCO_TEST_SSH_USER"), &var("INFCO_TEST_SSH_HASH")).unwrap();
+
+    let shell = "echo \"${BASH_VERSION:-no bash}\"";
+
+    session.set_deadline(Some(Instant::now() + std::time::Duration::from_millis(200)));
+    assert_eq!(session.file_read("/dev/zero".into()).unwrap_err().to_string(), "operation timed out");
+    session.set_deadline(None);
+
+    let output = session.run_command(shell).unwrap().stdout;
+
+    session.set_deadline(Some(Instant::now() + std::time::Duration::from_secs(10)));
+    assert_eq!(session.run_command(shell).unwrap().stdout, output);
+    assert_eq!(session.file_read("/dev/null".into()).unwrap(), Vec::<u8>::new());
 }
 
 impl Session {
@@ -43,66 +62,122 @@ impl Session {
         session.set_option(ssh_options::SshOptionsHost, host.to_string()).unwrap();
         session.set_option(ssh_options::SshOptionsUser, user.to_string()).unwrap();
         session.connect().unwrap();
-
-        let fingerprint = session.get_server_hash()?;
-
-        if hash != fingerprint {
-            return Err(SshError::new(&*format!("server public key hash did not match; expected: \"{}\"; found: \"{}\"", hash, fingerprint)).into());
-        }
-
+        session.hash = hash.to_string();
+        session.verify_server_hash()?;
         session.authenticate().unwrap();
 
         Ok(session)
     }
 
-    pub fn run_command(&mut self, command: &str) -> Result<String, Box<dyn Error>> {
+    /// Sets the deadline for the following operations; `None` removes it.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Runs a command; with a deadline, the command is run under `timeout`, so that it stops on the remote side even if the signal sent when the deadline passes is ignored.
+    ///
+    /// The command is run by the shell of the user, as it is without a deadline.
+    pub fn run_command(&mut self, command: &str) -> Result<CommandOutput, Box<dyn Error>> {
+        self.ensure_connected()?;
+
+        let command = match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline.checked_duration_since(Instant::now()).ok_or(SshError::new("command timed out"))?;
+
+                format!("timeout -k 5 {} \"${{SHELL:-sh}}\" -c '{}'", remaining.as_secs() + 1, command.replace('\'', "'\\''"))
+            },
+            None => command.to_string()
+        };
         let mut channel = self.get_channel()?;
 
         channel.open_session()?;
-        channel.request_exec(command)?;
+        channel.request_exec(&command)?;
         channel.send_eof()?;
-        Ok(String::from_utf8(channel.read()?)?)
+
+        let stdout = String::from_utf8(channel.read(self.deadline)?)?;
+        let stderr = String::from_utf8(channel.read_stderr()?)?;
+
+        Ok(CommandOutput { stdout, stderr, exit_code: channel.get_exit_status() })
     }
 
     pub fn file_read(&mut self, path: String) -> Result<Vec<u8>, Box<dyn Error>> {
-        let sftp_session = self.get_sftp_session()?;
-        let sftp_file = sftp_session.open_file(&CString::new(path)?, libc::O_RDONLY, 0)?;
+        self.run_under_deadline(|session| {
+            let sftp_session = session.get_sftp_session()?;
+            let sftp_file = sftp_session.open_file(&CString::new(path)?, libc::O_RDONLY, 0)?;
 
-        sftp_file.read()
+            sftp_file.read()
+        })
     }
 
     pub fn file_write(&mut self, path: String, data: Vec<u8>) -> Result<(), Box<dyn Error>> {
-        let sftp_session = self.get_sftp_session()?;
-        let mut sftp_file = sftp_session.open_file(&CString::new(path)?, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC, libc::S_IRUSR | libc::S_IWUSR | libc::S_IRGRP | libc::S_IROTH)?;
+        self.run_under_deadline(|session| {
+            let sftp_session = session.get_sftp_session()?;
+            let mut sftp_file = sftp_session.open_file(&CString::new(path)?, libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC, libc::S_IRUSR | libc::S_IWUSR | libc::S_IRGRP | libc::S_IROTH)?;
+
+            sftp_file.write(&data[..])
+        })
+    }
+
+    pub fn dir_read(&mut self, path: String) -> Result<Vec<DirEntry>, Box<dyn Error>> {
+        self.run_under_deadline(|session| {
+            let sftp_session = session.get_sftp_session()?;
+            let sftp_dir = sftp_session.open_dir(&CString::new(path)?)?;
+
+            sftp_dir.read()
+        })
+    }
+
+    pub fn dir_create(&mut self, path: String) -> Result<(), Box<dyn Error>> {
+        self.run_under_deadline(|session| session.get_sftp_session()?.mkdir(&CString::new(path)?, libc::S_IRWXU | libc::S_IRGRP | libc::S_IXGRP | libc::S_IROTH | libc::S_IXOTH))
+    }
+
+    pub fn dir_remove(&mut self, path: String) -> Result<(), Box<dyn Error>> {
+        self.run_under_deadline(|session| session.get_sftp_session()?.rmdir(&CString::new(path)?))
+    }
 
-        sftp_file.write(&data[..])
+    pub fn file_remove(&mut self, path: String) -> Result<(), Box<dyn Error>> {
+        self.run_under_deadline(|session| session.get_sftp_session()?.unlink(&CString::new(path)?))
     }
 
-    pub async fn run_socket_request(&mut self, request_type: RequestType, request: Request) -> Result<String, Box<dyn Error>> {
-        let mut channel = self.get_channel().unwrap();
+    pub fn file_set_mode(&mut self, path: String, mode: u32) -> Result<(), Box<dyn Error>> {
+        self.run_under_deadline(|session| session.get_sftp_session()?.chmod(&CString::new(path)?, mode as libc::mode_t))
+    }
+
+    pub async fn run_socket_request(&mut self, request_type: RequestType, request: Request) -> Result<Response, Box<dyn Error>> {
+        self.ensure_connected()?;
+
+        let mut channel = self.get_channel()?;
 
         match request_type {
-            RequestType::Socket(path) => channel.forward_socket(path.as_str()).unwrap(),
-            RequestType::HostPort(host, port) => channel.forward_host_port(host.as_str(), port as i32).unwrap()
+            RequestType::Socket(path) => channel.forward_socket(path.as_str())?,
+            RequestType::HostPort(host, port) => channel.forward_host_port(host.as_str(), port as i32)?
         }
 
-        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
         let local_port = listener.local_addr()?.port();
         let lis = listener.accept();
         let client = hyper::Client::new();
-        let req = hyper::Request::builder()
+        let mut builder = hyper::Request::builder()
             .method(request.method.as_str())
-            .uri(format!("http://127.0.0.1:{}{}", local_port, request.path))
-            .body(match request.body {
+            .uri(format!("http://127.0.0.1:{}{}", local_port, request.path));
+
+        for (name, value) in request.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+
+        let req = builder.body(match request.body {
                 Some(vec) => hyper::Body::from(vec),
                 None => hyper::Body::empty()
             })?;
         let hdnl = tokio::spawn(async move {
-            let resp = client.request(req).await.unwrap();
-            String::from_utf8(hyper::body::to_bytes(resp).await.unwrap().to_vec())
+            let resp = client.request(req).await?;
+            let status = resp.status().as_u16();
+            let body = hyper::body::to_bytes(resp).await?.to_vec();
+
+            Ok::<Response, hyper::Error>(Response { status, body })
         });
     
-        let (mut socket, _) = lis.await.unwrap();
+        let (mut socket, _) = lis.await?;
         const BUFFER_SIZE: usize = 1024;
         let mut data = [0; BUFFER_SIZE];
         let mut data1 = [0; BUFFER_SIZE + 1];
@@ -118,11 +193,11 @@ impl Session {
         }
     
         channel.write(&output[..])?;
-        channel.send_eof().unwrap();
+        channel.send_eof()?;
 
-        let resp = channel.read()?;
+        let resp = channel.read(None)?;
 
-        socket.write(&resp[..]).await?;
+        socket.write_all(&resp[..]).await?;
 
         let resp = hdnl.await?;
         Ok(resp?)
@@ -137,6 +212,9 @@ impl Session {
 
         let session = Session {
             ptr: Arc::new(Mutex::new(ptr)),
+            hash: String::new(),
+            deadline: None,
+            disconnected: false,
         };
 
         Ok(session)
@@ -154,6 +232,65 @@ impl Session {
         }
     }
 
+    /// Runs a blocking operation under the deadline, if any; libssh waits for SFTP replies without a timeout, so when the deadline passes, the connection is shut down, which makes the operation fail, and established again before the next operation.
+    fn run_under_deadline<T>(&mut self, operation: impl FnOnce(&mut Session) -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+        self.ensure_connected()?;
+
+        let remaining = match self.deadline {
+            Some(deadline) => deadline.checked_duration_since(Instant::now()).ok_or(SshError::new("operation timed out"))?,
+            None => return operation(self)
+        };
+        let fd = unsafe { wrapper::ssh_get_fd(*self.ptr.lock().unwrap()) };
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+        let watchdog = std::thread::spawn(move || match done_rx.recv_timeout(remaining) {
+            Err(RecvTimeoutError::Timeout) => {
+                unsafe { libc::shutdown(fd, libc::SHUT_RDWR) };
+                true
+            },
+            _ => false
+        });
+        let res = operation(self);
+
+        drop(done_tx);
+
+        if watchdog.join().unwrap_or(false) {
+            self.disconnected = true;
+
+            return Err(SshError::new("operation timed out").into());
+        }
+
+        res
+    }
+
+    /// Connects again if the connection was shut down because of a deadline.
+    fn ensure_connected(&mut self) -> Result<(), Box<dyn Error>> {
+        match self.disconnected {
+            true => self.reconnect(),
+            false => Ok(())
+        }
+    }
+
+    fn reconnect(&mut self) -> Result<(), Box<dyn Error>> {
+        unsafe { wrapper::ssh_disconnect(*self.ptr.lock().unwrap()) };
+
+        self.connect()?;
+        self.verify_server_hash()?;
+        self.authenticate()?;
+        self.disconnected = false;
+
+        Ok(())
+    }
+
+    fn verify_server_hash(&mut self) -> Result<(), Box<dyn Error>> {
+        let fingerprint = self.get_server_hash()?;
+
+        if self.hash != fingerprint {
+            return Err(SshError::new(&*format!("server public key hash did not match; expected: \"{}\"; found: \"{}\"", self.hash, fingerprint)).into());
+        }
+
+        Ok(())
+    }
+
     fn connect(&mut self) -> Result<(), Box<dyn Error>> {
         match unsafe { wrapper::ssh_connect(*self.ptr.lock().unwrap()) } {
             wrapper::ssh_result::SshOk => Ok(()),
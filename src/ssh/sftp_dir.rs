@@ -0,0 +1,53 @@
+use super::wrapper;
+use super::error::SshError;
+use crate::service::DirEntry;
+use std::ffi::CStr;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+pub struct SftpDir {
+    pub session_ptr: Arc<Mutex<*mut libc::c_void>>,
+    pub ptr: Arc<Mutex<*mut libc::c_void>>,
+}
+
+impl SftpDir {
+    pub fn read(&self) -> Result<Vec<DirEntry>, Box<dyn Error>> {
+        let mut entries = Vec::new();
+
+        loop {
+            let attributes = unsafe { wrapper::sftp_readdir(*self.session_ptr.lock().unwrap(), *self.ptr.lock().unwrap()) };
+
+            if attributes.is_null() {
+                break;
+            }
+
+            let entry = unsafe {
+                let name = CStr::from_ptr((*attributes).name).to_str().map(String::from);
+                let entry = name.map(|name| DirEntry {
+                    name,
+                    is_dir: (*attributes).file_type == wrapper::sftp_file_type::Directory as u8,
+                    size: (*attributes).size,
+                    mode: (*attributes).permissions & 0o7777,
+                });
+
+                wrapper::sftp_attributes_free(attributes);
+                entry?
+            };
+
+            if entry.name != "." && entry.name != ".." {
+                entries.push(entry);
+            }
+        }
+
+        match unsafe { wrapper::sftp_dir_eof(*self.ptr.lock().unwrap()) } {
+            0 => Err(SshError::new("error reading directory").into()),
+            _ => Ok(entries)
+        }
+    }
+}
+
+impl Drop for SftpDir {
+    fn drop(&mut self) {
+        unsafe { wrapper::sftp_closedir(*self.ptr.lock().unwrap()) };
+    }
+}
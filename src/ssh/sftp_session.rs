@@ -1,22 +1,65 @@
-use super::{sftp_file::SftpFile, wrapper};
+use super::{sftp_file::SftpFile, sftp_dir::SftpDir, wrapper};
 use super::error::SshError;
 use std::ffi::{CString};
 use std::error::Error;
 use std::sync::{Arc, Mutex};
 
+const SSH_FX_NO_SUCH_FILE: libc::c_int = 2;
+
 pub struct SftpSession {
     pub ptr: Arc<Mutex<*mut libc::c_void>>,
 }
 
 impl SftpSession {
     pub fn open_file(&self, filename: &CString, accesstype: libc::c_int, mode: libc::mode_t) -> Result<SftpFile, Box<dyn Error>> {
-        let ptr = unsafe { wrapper::sftp_open(*self.ptr.lock().unwrap(), filename.as_ptr(), accesstype, mode) };
+        let session_ptr = *self.ptr.lock().unwrap();
+        let ptr = unsafe { wrapper::sftp_open(session_ptr, filename.as_ptr(), accesstype, mode) };
 
         match ptr.is_null() {
+            true if unsafe { wrapper::sftp_get_error(session_ptr) } == SSH_FX_NO_SUCH_FILE => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no such file").into()),
             true => Err(SshError::new("error opening file").into()),
             false => Ok(SftpFile {ptr: Arc::new(Mutex::new(ptr))})
         }
     }
+
+    pub fn open_dir(&self, path: &CString) -> Result<SftpDir, Box<dyn Error>> {
+        let session_ptr = *self.ptr.lock().unwrap();
+        let ptr = unsafe { wrapper::sftp_opendir(session_ptr, path.as_ptr()) };
+
+        match ptr.is_null() {
+            true if unsafe { wrapper::sftp_get_error(session_ptr) } == SSH_FX_NO_SUCH_FILE => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no such directory").into()),
+            true => Err(SshError::new("error opening directory").into()),
+            false => Ok(SftpDir {session_ptr: self.ptr.clone(), ptr: Arc::new(Mutex::new(ptr))})
+        }
+    }
+
+    pub fn mkdir(&self, path: &CString, mode: libc::mode_t) -> Result<(), Box<dyn Error>> {
+        match unsafe { wrapper::sftp_mkdir(*self.ptr.lock().unwrap(), path.as_ptr(), mode) } {
+            wrapper::ssh_result::SshOk => Ok(()),
+            _ => Err(SshError::new("error creating directory").into())
+        }
+    }
+
+    pub fn rmdir(&self, path: &CString) -> Result<(), Box<dyn Error>> {
+        match unsafe { wrapper::sftp_rmdir(*self.ptr.lock().unwrap(), path.as_ptr()) } {
+            wrapper::ssh_result::SshOk => Ok(()),
+            _ => Err(SshError::new("error removing directory").into())
+        }
+    }
+
+    pub fn unlink(&self, path: &CString) -> Result<(), Box<dyn Error>> {
+        match unsafe { wrapper::sftp_unlink(*self.ptr.lock().unwrap(), path.as_ptr()) } {
+            wrapper::ssh_result::SshOk => Ok(()),
+            _ => Err(SshError::new("error removing file").into())
+        }
+    }
+
+    pub fn chmod(&self, path: &CString, mode: libc::mode_t) -> Result<(), Box<dyn Error>> {
+        match unsafe { wrapper::sftp_chmod(*self.ptr.lock().unwrap(), path.as_ptr(), mode) } {
+            wrapper::ssh_result::SshOk => Ok(()),
+            _ => Err(SshError::new("error setting mode").into())
+        }
+    }
 }
 
 impl Drop for SftpSession {
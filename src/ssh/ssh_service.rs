@@ -1,20 +1,46 @@
-use crate::service::Service;
+use crate::service::{Service, Request, Response, DirEntry, CommandOutput, is_not_found};
 use async_trait::async_trait;
 use tokio::sync::{oneshot, mpsc};
 use tokio::runtime::Runtime;
 use tokio::task;
-use super::{error::SshError, session::Session};
+use std::time::Instant;
+use super::{error::SshError, session::{Session, RequestType}};
 
 enum Command {
     Command { command: String },
     FileRead { path: String },
     FileWrite { path: String, data: Vec<u8> },
+    DirRead { path: String },
+    DirCreate { path: String },
+    DirRemove { path: String },
+    FileRemove { path: String },
+    FileSetMode { path: String, mode: u32 },
+    SocketRequest { socket: String, request: Request },
 }
 
-type CommandResponse = Option<Vec<u8>>;
+enum ResponseData {
+    Empty,
+    Data(Vec<u8>),
+    Output(CommandOutput),
+    Entries(Vec<DirEntry>),
+    Response(Response),
+}
+
+type CommandResponse = Result<ResponseData, std::io::Error>;
+
+/// Turns an error of the session into an error that can be sent to the service; a missing file stays recognisable as such.
+fn into_response_error(err: Box<dyn std::error::Error>) -> std::io::Error {
+    match err.downcast::<std::io::Error>() {
+        Ok(err) => *err,
+        Err(err) => std::io::Error::other(err.to_string())
+    }
+}
 
 pub struct SshService {
-    cmd_tx: mpsc::Sender<(Command, oneshot::Sender<CommandResponse>)>,
+    cmd_tx: mpsc::Sender<(Command, Option<Instant>, oneshot::Sender<CommandResponse>)>,
+    check_mode: bool,
+    deadline: Option<Instant>,
+    temp_files: Vec<String>,
 }
 
 impl SshService {
@@ -22,27 +48,53 @@ impl SshService {
         Session::get_server_fingerprint(host, user)
     }
 
-    pub fn new(host: String, user: String, hash: String) -> Result<Self, Box<dyn std::error::Error>> {
-        let (cmd_tx, mut cmd_rx) = mpsc::channel::<(Command, oneshot::Sender<CommandResponse>)>(100);
+    pub fn new(host: String, user: String, hash: String, check_mode: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel::<(Command, Option<Instant>, oneshot::Sender<CommandResponse>)>(100);
         tokio::task::spawn_blocking(|| {
             let rt  = Runtime::new().unwrap();
             let local = task::LocalSet::new();
     
             local.block_on(&rt, async move {
                 let mut session = Session::new_with_host_user_hash(&*host, &*user, &*hash).unwrap();
-                while let Some((cmd, response)) = cmd_rx.recv().await {
+                while let Some((cmd, deadline, response)) = cmd_rx.recv().await {
+                    session.set_deadline(deadline);
+
                     match cmd {
-                        /// TODO: Error handling
                         Command::Command{command} => {
-                            response.send(Some(Vec::from(session.run_command(&*command).unwrap().as_bytes()))).unwrap();
+                            let res = session.run_command(&*command).map(ResponseData::Output);
+                            response.send(res.map_err(into_response_error)).ok();
                         },
                         Command::FileRead{path} => {
-                            let res = session.file_read(path);
-                            response.send(Some(res.unwrap())).unwrap()
+                            let res = session.file_read(path).map(ResponseData::Data);
+                            response.send(res.map_err(into_response_error)).ok();
                         },
                         Command::FileWrite{path, data} => {
-                            session.file_write(path, data).unwrap();
-                            response.send(None).unwrap()
+                            let res = session.file_write(path, data).map(|_| ResponseData::Empty);
+                            response.send(res.map_err(into_response_error)).ok();
+                        },
+                        Command::DirRead{path} => {
+                            let res = session.dir_read(path).map(ResponseData::Entries);
+                            response.send(res.map_err(into_response_error)).ok();
+                        },
+                        Command::DirCreate{path} => {
+                            let res = session.dir_create(path).map(|_| ResponseData::Empty);
+                            response.send(res.map_err(into_response_error)).ok();
+                        },
+                        Command::DirRemove{path} => {
+                            let res = session.dir_remove(path).map(|_| ResponseData::Empty);
+                            response.send(res.map_err(into_response_error)).ok();
+                        },
+                        Command::FileRemove{path} => {
+                            let res = session.file_remove(path).map(|_| ResponseData::Empty);
+                            response.send(res.map_err(into_response_error)).ok();
+                        },
+                        Command::FileSetMode{path, mode} => {
+                            let res = session.file_set_mode(path, mode).map(|_| ResponseData::Empty);
+                            response.send(res.map_err(into_response_error)).ok();
+                        },
+                        Command::SocketRequest{socket, request} => {
+                            let res = session.run_socket_request(RequestType::Socket(socket), request).await.map(ResponseData::Response);
+                            response.send(res.map_err(into_response_error)).ok();
                         },
                         // Command::Terminate => {
                         //     response.send(None).unwrap();
@@ -55,39 +107,145 @@ impl SshService {
         
         Ok(SshService {
             cmd_tx: cmd_tx,
+            check_mode: check_mode,
+            deadline: None,
+            temp_files: Vec::new(),
         })
     }
 
-    async fn send_command(&mut self, command: Command) -> Result<CommandResponse, Box<dyn std::error::Error>> {
+    fn refuse_in_check_mode(&self, operation: &str) -> Result<(), SshError> {
+        match self.check_mode {
+            true => Err(SshError::new(&*format!("refusing to {} in check mode", operation))),
+            false => Ok(())
+        }
+    }
+
+    async fn send_command(&mut self, command: Command) -> Result<ResponseData, Box<dyn std::error::Error>> {
         let (resp_tx, resp_rx) = oneshot::channel();
-        self.cmd_tx.send((command, resp_tx)).await.ok();
-        resp_rx.await.map_err(|e| e.into())
+        self.cmd_tx.send((command, self.deadline, resp_tx)).await.ok();
+        resp_rx.await?.map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => err.into(),
+            _ => SshError::new(&*err.to_string()).into()
+        })
     }
 }
 
 #[async_trait]
 impl Service for SshService {
-    async fn run(&mut self, command: String) -> Result<String, Box<dyn std::error::Error>> {
+    fn check_mode(&self) -> bool {
+        self.check_mode
+    }
+
+    fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    async fn query(&mut self, command: String) -> Result<CommandOutput, Box<dyn std::error::Error>> {
         match self.send_command(Command::Command{command: command}).await {
-            Ok(Some(res)) => Ok(String::from_utf8(res)?),
-            Ok(None) => Err(SshError::new("unexpected result").into()),
+            Ok(ResponseData::Output(output)) => output.into_result(),
+            Ok(_) => Err(SshError::new("unexpected result").into()),
             Err(err) => Err(err)
         }
     }
 
+    async fn run(&mut self, command: String) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("run command")?;
+        self.query(command).await
+    }
+
     async fn file_read(&mut self, path: String) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         match self.send_command(Command::FileRead{path: path}).await {
-            Ok(Some(data)) => Ok(data),
-            Ok(None) => Err(SshError::new("no data read").into()),
+            Ok(ResponseData::Data(data)) => Ok(data),
+            Ok(_) => Err(SshError::new("no data read").into()),
             Err(err) => Err(err)
         }
     }
 
     async fn file_write(&mut self, path: String, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("write file")?;
         match self.send_command(Command::FileWrite{path: path, data: data}).await {
-            Ok(Some(_)) => Err(SshError::new("received unexpected result while writing data").into()),
-            Ok(None) => Ok(()),
+            Ok(ResponseData::Empty) => Ok(()),
+            Ok(_) => Err(SshError::new("received unexpected result while writing data").into()),
+            Err(err) => Err(err)
+        }
+    }
+
+    async fn dir_read(&mut self, path: String) -> Result<Vec<DirEntry>, Box<dyn std::error::Error>> {
+        match self.send_command(Command::DirRead{path: path}).await {
+            Ok(ResponseData::Entries(entries)) => Ok(entries),
+            Ok(_) => Err(SshError::new("received unexpected result while reading directory").into()),
+            Err(err) => Err(err)
+        }
+    }
+
+    async fn dir_create(&mut self, path: String) -> Result<(), Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("create directory")?;
+        match self.send_command(Command::DirCreate{path: path}).await {
+            Ok(ResponseData::Empty) => Ok(()),
+            Ok(_) => Err(SshError::new("received unexpected result while creating directory").into()),
+            Err(err) => Err(err)
+        }
+    }
+
+    async fn dir_remove(&mut self, path: String) -> Result<(), Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("remove directory")?;
+        match self.send_command(Command::DirRemove{path: path}).await {
+            Ok(ResponseData::Empty) => Ok(()),
+            Ok(_) => Err(SshError::new("received unexpected result while removing directory").into()),
+            Err(err) => Err(err)
+        }
+    }
+
+    async fn file_remove(&mut self, path: String) -> Result<(), Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("remove file")?;
+        match self.send_command(Command::FileRemove{path: path.clone()}).await {
+            Ok(ResponseData::Empty) => {
+                self.temp_files.retain(|temp_file| *temp_file != path);
+                Ok(())
+            },
+            Ok(_) => Err(SshError::new("received unexpected result while removing file").into()),
+            Err(err) => Err(err)
+        }
+    }
+
+    async fn file_set_mode(&mut self, path: String, mode: u32) -> Result<(), Box<dyn std::error::Error>> {
+        self.refuse_in_check_mode("set mode")?;
+        match self.send_command(Command::FileSetMode{path: path, mode: mode}).await {
+            Ok(ResponseData::Empty) => Ok(()),
+            Ok(_) => Err(SshError::new("received unexpected result while setting mode").into()),
             Err(err) => Err(err)
         }
     }
+
+    async fn socket_request(&mut self, socket: String, request: Request) -> Result<Response, Box<dyn std::error::Error>> {
+        if request.method != "GET" {
+            self.refuse_in_check_mode("send request")?;
+        }
+
+        match self.send_command(Command::SocketRequest{socket: socket, request: request}).await {
+            Ok(ResponseData::Response(response)) => Ok(response),
+            Ok(_) => Err(SshError::new("received unexpected result while sending request").into()),
+            Err(err) => Err(err)
+        }
+    }
+
+    fn add_temp_file(&mut self, path: String) {
+        self.temp_files.push(path);
+    }
+
+    async fn remove_temp_files(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut failed = Vec::new();
+
+        for path in std::mem::take(&mut self.temp_files) {
+            match self.file_remove(path.clone()).await {
+                Err(err) if !is_not_found(err.as_ref()) => failed.push(format!("\"{}\" ({})", path, err)),
+                _ => {}
+            }
+        }
+
+        match failed.is_empty() {
+            true => Ok(()),
+            false => Err(SshError::new(&*format!("could not remove {}", failed.join(", "))).into())
+        }
+    }
 }
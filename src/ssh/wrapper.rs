@@ -98,6 +98,30 @@ pub enum sftp_access_type {
     Truncate = 1000
 }
 
+#[repr(C)]
+pub enum sftp_file_type {
+    Regular = 1,
+    Directory = 2,
+    Symlink = 3,
+    Special = 4,
+    Unknown = 5,
+}
+
+/// Leading fields of `struct sftp_attributes_struct`; the structure is only ever accessed through pointers returned by libssh.
+#[repr(C)]
+pub struct sftp_attributes {
+    pub name: *const libc::c_char,
+    pub longname: *const libc::c_char,
+    pub flags: u32,
+    pub file_type: u8,
+    pub size: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub owner: *const libc::c_char,
+    pub group: *const libc::c_char,
+    pub permissions: u32,
+}
+
 use std::sync::{Arc, Mutex};
 
 #[link(name = "ssh")]
@@ -108,6 +132,7 @@ extern {
     pub fn ssh_connect(session: *mut libc::c_void) -> ssh_result;
     pub fn ssh_disconnect(session: *mut libc::c_void) -> ();
     pub fn ssh_is_connected(session: *mut libc::c_void) -> libc::c_int;
+    pub fn ssh_get_fd(session: *mut libc::c_void) -> libc::c_int;
     pub fn ssh_userauth_publickey_auto(session: *mut libc::c_void, user: *const libc::c_void, pass: *const libc::c_void) -> ssh_auth_result;
     pub fn ssh_session_is_known_server(session: *mut libc::c_void) -> ssh_known_hosts;
     pub fn ssh_set_blocking(session: *mut libc::c_void, blocking: libc::c_int) -> *mut libc::c_void;
@@ -117,13 +142,16 @@ extern {
     pub fn ssh_channel_open_session(channel: *mut libc::c_void) -> ssh_result;
     pub fn ssh_channel_close(channel: *mut libc::c_void) -> ssh_result;
     pub fn ssh_channel_request_exec(channel: *mut libc::c_void, cmd: *const libc::c_void) -> ssh_result;
+    pub fn ssh_channel_request_send_signal(channel: *mut libc::c_void, signum: *const libc::c_char) -> ssh_result;
     pub fn ssh_channel_read(channel: *mut libc::c_void, dest: *mut libc::c_void, count: u32, is_stderr: libc::c_int) -> libc::c_int;
+    pub fn ssh_channel_read_timeout(channel: *mut libc::c_void, dest: *mut libc::c_void, count: u32, is_stderr: libc::c_int, timeout_ms: libc::c_int) -> libc::c_int;
     pub fn ssh_channel_open_forward_unix(channel: *mut libc::c_void, remotepath: *const libc::c_void, sourcehost: *const libc::c_void, localport: libc::c_int) -> ssh_result;
     pub fn ssh_channel_write(channel: *mut libc::c_void, data: *const libc::c_char, length: u32) -> libc::c_int;
     pub fn ssh_channel_send_eof(channel: *mut libc::c_void) -> ssh_result;
     pub fn ssh_channel_open_forward(channel: *mut libc::c_void, remotehost: *const libc::c_char, remoteport: libc::c_int, sourcehost: *const libc::c_char, localport: libc::c_int) -> ssh_result;
     pub fn ssh_channel_is_eof(channel: *mut libc::c_void) -> libc::c_int;
     pub fn ssh_channel_is_open(channel: *mut libc::c_void) -> libc::c_int;
+    pub fn ssh_channel_get_exit_status(channel: *mut libc::c_void) -> libc::c_int;
     pub fn ssh_get_server_publickey(session: *mut libc::c_void, key: *mut *mut libc::c_void) -> ssh_result;
     pub fn ssh_get_publickey_hash(key: *const libc::c_void, hash_type: ssh_publickey_hash_type, hash: *mut *mut libc::c_char, length: *mut libc::size_t) -> ssh_result;
     pub fn ssh_get_fingerprint_hash(hash_type: ssh_publickey_hash_type, hash: *const libc::c_char, len: libc::size_t) -> *const libc::c_char;
@@ -136,6 +164,17 @@ extern {
     pub fn sftp_init(sftp_session: *mut libc::c_void) -> ssh_result;
     pub fn sftp_open(sftp_session: *mut libc::c_void, file: *const libc::c_char, accesstype: libc::c_int, mode: libc::mode_t) -> *mut libc::c_void;
     pub fn sftp_free(sftp_session: *mut libc::c_void);
+    pub fn sftp_get_error(sftp_session: *mut libc::c_void) -> libc::c_int;
+    pub fn sftp_opendir(sftp_session: *mut libc::c_void, path: *const libc::c_char) -> *mut libc::c_void;
+    pub fn sftp_readdir(sftp_session: *mut libc::c_void, dir: *mut libc::c_void) -> *mut sftp_attributes;
+    pub fn sftp_mkdir(sftp_session: *mut libc::c_void, directory: *const libc::c_char, mode: libc::mode_t) -> ssh_result;
+    pub fn sftp_rmdir(sftp_session: *mut libc::c_void, directory: *const libc::c_char) -> ssh_result;
+    pub fn sftp_unlink(sftp_session: *mut libc::c_void, file: *const libc::c_char) -> ssh_result;
+    pub fn sftp_chmod(sftp_session: *mut libc::c_void, file: *const libc::c_char, mode: libc::mode_t) -> ssh_result;
+    pub fn sftp_attributes_free(attributes: *mut sftp_attributes);
+    // sftp dir
+    pub fn sftp_dir_eof(dir: *mut libc::c_void) -> libc::c_int;
+    pub fn sftp_closedir(dir: *mut libc::c_void) -> ssh_result;
     // sftp file
     pub fn sftp_read(sftp_file: *mut libc::c_void, buf: *mut libc::c_void, count: libc::size_t) -> libc::ssize_t;
     pub fn sftp_write(sftp_file: *mut libc::c_void, buf: *const libc::c_void, count: libc::size_t) -> libc::ssize_t;
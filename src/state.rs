@@ -0,0 +1,176 @@
+use crate::error::InfcoError;
+use log::error;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// The progress of a host; `next_task` is the index of the first task that has not been completed.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HostState {
+    pub completed: bool,
+    pub next_task: usize,
+    pub notified: Vec<String>,
+    pub registered: Map<String, Value>,
+}
+
+/// The progress of a run, which is written to the state file, if any, on every update.
+///
+/// The state is bound to the tasks file by its hash and to the selection of tasks and hosts, so that a run cannot be resumed with changed tasks or a different selection; otherwise, a host whose tasks were left out would be considered completed.
+pub struct State {
+    path: Option<String>,
+    tasks_hash: String,
+    selection: Value,
+    hosts: BTreeMap<String, HostState>,
+    writer: Option<Writer>,
+}
+
+#[test]
+fn function_from_value() {
+    let mut state = State::new(None, "abc", json!({"tags": ["web"]}));
+
+    let mut registered = Map::new();
+
+    registered.insert("version".into(), json!({"stdout": "1.2.3\n"}));
+    state.update("web1", HostState { completed: false, next_task: 3, notified: vec!["restart nginx".into()], registered: registered.clone() });
+    state.update("web2", HostState { completed: true, next_task: 5, notified: Vec::new(), registered: Map::new() });
+
+    let restored = State::from_value(&state.to_value()).unwrap();
+
+    assert_eq!(restored.tasks_hash, "abc");
+    assert_eq!(restored.selection, json!({"tags": ["web"]}));
+    assert_eq!(restored.host("web1"), HostState { completed: false, next_task: 3, notified: vec!["restart nginx".into()], registered });
+    assert!(restored.host("web2").completed);
+    assert_eq!(restored.host("db1"), HostState::default());
+    assert!(State::from_value(&json!({"hosts": {}})).is_err());
+}
+
+impl State {
+    /// Creates an empty state; with a path, the state file is written in the background, which requires a Tokio runtime.
+    pub fn new(path: Option<&str>, tasks_hash: &str, selection: Value) -> Self {
+        State {
+            path: path.map(String::from),
+            tasks_hash: tasks_hash.into(),
+            selection,
+            hosts: BTreeMap::new(),
+            writer: path.map(Writer::start),
+        }
+    }
+
+    pub fn load(path: &str, tasks_hash: &str, selection: Value) -> Result<Self, Box<dyn std::error::Error>> {
+        let value: Value = serde_json::from_str(&*read_to_string(path)?)?;
+        let mut state = Self::from_value(&value)?;
+
+        if state.tasks_hash != tasks_hash {
+            return Err(InfcoError::new(&*format!("the tasks have changed since the state file \"{}\" was written", path)).into());
+        }
+
+        if state.selection != selection {
+            return Err(InfcoError::new(&*format!("the selected tags or hosts differ from the ones of the run recorded in the state file \"{}\"", path)).into());
+        }
+
+        state.path = Some(path.into());
+        state.writer = Some(Writer::start(path));
+
+        Ok(state)
+    }
+
+    pub fn host(&self, title: &str) -> HostState {
+        self.hosts.get(title).cloned().unwrap_or_default()
+    }
+
+    /// Updates the progress of a host and has the state file written; failing to write the file is logged, but does not interrupt the run.
+    pub fn update(&mut self, title: &str, host_state: HostState) {
+        self.hosts.insert(title.into(), host_state);
+
+        if let (Some(path), Some(writer)) = (&self.path, &self.writer) {
+            match serde_json::to_string_pretty(&self.to_value()) {
+                Ok(content) => {
+                    writer.content_tx.send((title.to_string(), content)).ok();
+                },
+                Err(err) => error!("[{}] error writing state file \"{}\": {}", title, path, err)
+            }
+        }
+    }
+
+    /// Stops writing the state file; the returned task writes the pending updates and has to be awaited before the program exits.
+    pub fn close(&mut self) -> Option<JoinHandle<()>> {
+        self.writer.take().map(|writer| writer.handle)
+    }
+
+    fn to_value(&self) -> Value {
+        let mut hosts = Map::new();
+
+        for (title, host_state) in &self.hosts {
+            hosts.insert(title.clone(), json!({
+                "completed": host_state.completed,
+                "nextTask": host_state.next_task,
+                "notified": host_state.notified,
+                "registered": host_state.registered,
+            }));
+        }
+
+        json!({
+            "tasksHash": self.tasks_hash,
+            "selection": self.selection,
+            "hosts": hosts
+        })
+    }
+
+    fn from_value(value: &Value) -> Result<Self, InfcoError> {
+        let malformed = || InfcoError::new("malformed state file");
+        let mut hosts = BTreeMap::new();
+
+        for (title, host_state) in value["hosts"].as_object().ok_or_else(malformed)? {
+            hosts.insert(title.clone(), HostState {
+                completed: host_state["completed"].as_bool().ok_or_else(malformed)?,
+                next_task: host_state["nextTask"].as_u64().ok_or_else(malformed)? as usize,
+                notified: host_state["notified"].as_array().ok_or_else(malformed)?.iter()
+                    .map(|title| title.as_str().map(String::from).ok_or_else(malformed)).collect::<Result<Vec<String>, InfcoError>>()?,
+                registered: host_state["registered"].as_object().cloned().ok_or_else(malformed)?,
+            });
+        }
+
+        Ok(State {
+            path: None,
+            tasks_hash: value["tasksHash"].as_str().ok_or_else(malformed)?.into(),
+            selection: value["selection"].clone(),
+            hosts,
+            writer: None,
+        })
+    }
+}
+
+/// Writes the state file in a background task, so that the run is not blocked; only the latest content is written, and errors are attributed to the host of that update, to a temporary file that then replaces the state file, so that the state file is never left half-written.
+struct Writer {
+    content_tx: mpsc::UnboundedSender<(String, String)>,
+    handle: JoinHandle<()>,
+}
+
+impl Writer {
+    fn start(path: &str) -> Self {
+        let path = path.to_string();
+        let (content_tx, mut content_rx) = mpsc::unbounded_channel::<(String, String)>();
+        let handle = tokio::spawn(async move {
+            while let Some((mut title, mut content)) = content_rx.recv().await {
+                while let Ok(newer) = content_rx.try_recv() {
+                    (title, content) = newer;
+                }
+
+                if let Err(err) = write_atomically(&path, content).await {
+                    error!("[{}] error writing state file \"{}\": {}", title, path, err);
+                }
+            }
+        });
+
+        Writer { content_tx, handle }
+    }
+}
+
+async fn write_atomically(path: &str, content: String) -> std::io::Result<()> {
+    let temp_path = format!("{}.tmp", path);
+
+    tokio::fs::write(&temp_path, content).await?;
+    tokio::fs::rename(&temp_path, path).await
+}
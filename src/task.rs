@@ -1,3 +1,16 @@
 pub mod command;
 pub mod file_transfer;
+pub mod template;
+pub mod line_in_file;
+pub mod block_in_file;
+pub mod package;
+pub mod systemd;
+pub mod docker_container;
+pub mod user;
+pub mod group;
+pub mod unarchive;
+pub mod script;
+pub mod outcome;
+mod directory_sync;
 mod error;
+mod shell;
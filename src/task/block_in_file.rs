@@ -0,0 +1,81 @@
+use crate::Service;
+use crate::service::is_not_found;
+use serde_json::Value;
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use super::line_in_file::join_lines;
+
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let path = config["path"].as_str().ok_or(TaskError::new("error reading path"))?;
+    let marker = config["marker"].as_str().unwrap_or("# {mark} INFCO MANAGED BLOCK");
+    let begin = marker.replace("{mark}", "BEGIN");
+    let end = marker.replace("{mark}", "END");
+
+    if begin == end {
+        return Err(TaskError::new("marker must contain \"{mark}\"").into());
+    }
+
+    let content = match context.file_read(path.to_string()).await {
+        Ok(data) => String::from_utf8(data)?,
+        Err(err) if config["create"].as_bool().unwrap_or(false) && is_not_found(&*err) => String::new(),
+        Err(err) => return Err(err)
+    };
+    let new_content = match config["state"].as_str() {
+        Some("present") | None => ensure_block(&content, &begin, &end, Some(config["block"].as_str().ok_or(TaskError::new("error reading block"))?))?,
+        Some("absent") => ensure_block(&content, &begin, &end, None)?,
+        Some(state) => return Err(TaskError::new(&*format!("unknown state \"{}\" given", state)).into())
+    };
+
+    match new_content == content {
+        true => Ok(TaskOutcome::ok()),
+        false => {
+            if !context.check_mode() {
+                context.file_write(path.to_string(), new_content.into_bytes()).await?;
+            }
+
+            Ok(TaskOutcome::changed())
+        }
+    }
+}
+
+#[test]
+fn function_ensure_block() {
+    assert_eq!(ensure_block("a\n", "# BEGIN", "# END", Some("b\nc")).unwrap(), "a\n# BEGIN\nb\nc\n# END\n");
+    assert_eq!(ensure_block("a\n# BEGIN\nx\n# END\nd\n", "# BEGIN", "# END", Some("b\nc\n")).unwrap(), "a\n# BEGIN\nb\nc\n# END\nd\n");
+    assert_eq!(ensure_block("a\n# BEGIN\nb\n# END\nd", "# BEGIN", "# END", Some("b")).unwrap(), "a\n# BEGIN\nb\n# END\nd");
+    assert_eq!(ensure_block("a\n# BEGIN\nx\n# END\nd\n", "# BEGIN", "# END", None).unwrap(), "a\nd\n");
+    assert_eq!(ensure_block("a\n", "# BEGIN", "# END", None).unwrap(), "a\n");
+    assert!(ensure_block("a\n# BEGIN\nb\n", "# BEGIN", "# END", Some("b")).is_err());
+    assert!(ensure_block("a\n# END\n# BEGIN\nb\n", "# BEGIN", "# END", None).is_err());
+    assert!(ensure_block("a\n# END\n", "# BEGIN", "# END", Some("b")).is_err());
+}
+
+/// Replaces the lines between the begin and end markers with the block or appends the block if the markers are not found.
+///
+/// If no block is given, the markers and the lines between them are removed. A marker without its counterpart is an error,
+/// as appending would add another block on every run.
+fn ensure_block(content: &str, begin: &str, end: &str, block: Option<&str>) -> Result<String, TaskError> {
+    let lines: Vec<&str> = content.lines().collect();
+    let range = match (lines.iter().position(|l| *l == begin), lines.iter().position(|l| *l == end)) {
+        (Some(start), Some(stop)) if start < stop => Some((start, stop)),
+        (None, None) => None,
+        (Some(_), _) => return Err(TaskError::new(&*format!("found marker \"{}\" without matching \"{}\"", begin, end))),
+        (None, Some(_)) => return Err(TaskError::new(&*format!("found marker \"{}\" without matching \"{}\"", end, begin)))
+    };
+    let mut new_block = Vec::new();
+
+    if let Some(block) = block {
+        new_block.push(begin);
+        new_block.extend(block.lines());
+        new_block.push(end);
+    }
+
+    let new_lines: Vec<&str> = match range {
+        Some((start, stop)) if lines[start..=stop] == new_block[..] => return Ok(content.to_string()),
+        Some((start, stop)) => lines[..start].iter().chain(new_block.iter()).chain(lines[stop + 1..].iter()).copied().collect(),
+        None if new_block.is_empty() => return Ok(content.to_string()),
+        None => lines.iter().chain(new_block.iter()).copied().collect()
+    };
+
+    Ok(join_lines(&new_lines))
+}
@@ -1,7 +1,35 @@
 use crate::Service;
 use serde_json::Value;
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use super::shell::{quote, succeeds};
 
-pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<(), Box<dyn std::error::Error>> {
-    context.run(config["command"].as_str().unwrap().to_string()).await.unwrap();
-    Ok(())
+/// Runs a command on the context.
+///
+/// The command is skipped if the path given by `creates` exists, if the path given by `removes` does not exist or if the command given by `unless` succeeds.
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let command = config["command"].as_str().ok_or(TaskError::new("error reading command"))?;
+
+    if let Some(creates) = config["creates"].as_str() {
+        if succeeds(context, &*format!("test -e {}", quote(creates))).await? {
+            return Ok(TaskOutcome::skipped(&*format!("\"{}\" exists", creates)));
+        }
+    }
+
+    if let Some(removes) = config["removes"].as_str() {
+        if !succeeds(context, &*format!("test -e {}", quote(removes))).await? {
+            return Ok(TaskOutcome::skipped(&*format!("\"{}\" does not exist", removes)));
+        }
+    }
+
+    if let Some(unless) = config["unless"].as_str() {
+        if succeeds(context, unless).await? {
+            return Ok(TaskOutcome::skipped(&*format!("\"{}\" succeeded", unless)));
+        }
+    }
+
+    match context.check_mode() {
+        true => Ok(TaskOutcome::changed()),
+        false => Ok(TaskOutcome::changed().with_output(context.run(command.to_string()).await?))
+    }
 }
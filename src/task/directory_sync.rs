@@ -0,0 +1,179 @@
+use crate::service::{Service, DirEntry, is_not_found};
+use serde_json::Value;
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use super::shell::quote;
+use tokio::fs::{read, read_dir};
+
+/// Mirrors the local directory tree to the context.
+///
+/// Files of the same size are compared by content and only written if they differ. Symlinks are rejected as the context cannot create them. Modes are given as octal strings keyed by the path relative to the local directory. In check mode, directories that would be created are treated as empty.
+pub async fn run(context: &mut Box<dyn Service>, local_path: &str, context_path: &str, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let delete = config["delete"].as_bool().unwrap_or(false);
+    let modes = match &config["modes"] {
+        Value::Null => Vec::new(),
+        Value::Object(map) => map.iter().map(|(path, mode)| Ok((path.clone(), parse_mode(mode)?))).collect::<Result<Vec<(String, u32)>, TaskError>>()?,
+        _ => return Err(TaskError::new("modes must be an object").into())
+    };
+    let get_mode = |path: &str| modes.iter().find(|(mode_path, _)| mode_path == path).map(|(_, mode)| *mode);
+    let check_mode = context.check_mode();
+    let mut changed = false;
+    let mut stack = vec![String::new()];
+
+    match context.dir_read(context_path.to_string()).await {
+        Ok(_) => {},
+        Err(err) if is_not_found(&*err) => {
+            if !check_mode {
+                context.run(format!("mkdir -p {}", quote(context_path))).await?;
+            }
+
+            changed = true;
+        },
+        Err(err) => return Err(err)
+    }
+
+    while let Some(relative_dir) = stack.pop() {
+        let local_dir = join(local_path, &relative_dir);
+        let remote_dir = join(context_path, &relative_dir);
+        let remote_entries = match check_mode {
+            true => context.dir_read(remote_dir.clone()).await.unwrap_or_default(),
+            false => context.dir_read(remote_dir.clone()).await?
+        };
+        let mut local_names = Vec::new();
+        let mut local_entries = read_dir(&local_dir).await?;
+
+        while let Some(local_entry) = local_entries.next_entry().await? {
+            let name = local_entry.file_name().to_string_lossy().to_string();
+            let relative_path = join(&relative_dir, &name);
+            let remote_path = join(&remote_dir, &name);
+            let remote_entry = remote_entries.iter().find(|entry| entry.name == name);
+            let file_type = local_entry.file_type().await?;
+
+            if file_type.is_symlink() {
+                return Err(TaskError::new(&*format!("symlink \"{}\" cannot be synchronized", local_entry.path().display())).into());
+            }
+
+            if file_type.is_dir() {
+                match remote_entry {
+                    Some(entry) if entry.is_dir => {},
+                    Some(_) => {
+                        if !check_mode {
+                            context.file_remove(remote_path.clone()).await?;
+                            context.dir_create(remote_path.clone()).await?;
+                        }
+
+                        changed = true;
+                    },
+                    None => {
+                        if !check_mode {
+                            context.dir_create(remote_path.clone()).await?;
+                        }
+
+                        changed = true;
+                    }
+                }
+
+                stack.push(relative_path.clone());
+            } else {
+                let data = read(local_entry.path()).await?;
+                let is_current = match remote_entry {
+                    Some(entry) if entry.is_dir => {
+                        if !check_mode {
+                            remove_tree(context, &remote_path).await?;
+                        }
+
+                        false
+                    },
+                    Some(entry) if entry.size == data.len() as u64 => context.file_read(remote_path.clone()).await? == data,
+                    _ => false
+                };
+
+                if !is_current {
+                    if !check_mode {
+                        context.file_write(remote_path.clone(), data).await?;
+                    }
+
+                    changed = true;
+                }
+            }
+
+            if let Some(mode) = get_mode(&relative_path) {
+                if remote_entry.map(|entry| entry.mode) != Some(mode) {
+                    if !check_mode {
+                        context.file_set_mode(remote_path, mode).await?;
+                    }
+
+                    changed = true;
+                }
+            }
+
+            local_names.push(name);
+        }
+
+        if delete {
+            for entry in remote_entries.iter().filter(|entry| !local_names.contains(&entry.name)) {
+                if !check_mode {
+                    remove_entry(context, &join(&remote_dir, &entry.name), entry).await?;
+                }
+
+                changed = true;
+            }
+        }
+    }
+
+    Ok(TaskOutcome::from_changed(changed))
+}
+
+async fn remove_entry(context: &mut Box<dyn Service>, path: &str, entry: &DirEntry) -> Result<(), Box<dyn std::error::Error>> {
+    match entry.is_dir {
+        true => remove_tree(context, path).await,
+        false => context.file_remove(path.to_string()).await
+    }
+}
+
+/// Removes a directory on the context including its content.
+async fn remove_tree(context: &mut Box<dyn Service>, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stack = vec![path.to_string()];
+    let mut dirs = Vec::new();
+
+    while let Some(dir) = stack.pop() {
+        for entry in context.dir_read(dir.clone()).await? {
+            let entry_path = join(&dir, &entry.name);
+
+            match entry.is_dir {
+                true => stack.push(entry_path),
+                false => context.file_remove(entry_path).await?
+            }
+        }
+
+        dirs.push(dir);
+    }
+
+    for dir in dirs.into_iter().rev() {
+        context.dir_remove(dir).await?;
+    }
+
+    Ok(())
+}
+
+fn join(base: &str, name: &str) -> String {
+    match (base, name) {
+        ("", name) => name.to_string(),
+        (base, "") => base.to_string(),
+        (base, name) => format!("{}/{}", base.trim_end_matches('/'), name)
+    }
+}
+
+#[test]
+fn function_parse_mode() {
+    assert_eq!(parse_mode(&Value::from("0755")).unwrap(), 0o755);
+    assert_eq!(parse_mode(&Value::from("640")).unwrap(), 0o640);
+    assert!(parse_mode(&Value::from("0855")).is_err());
+    assert!(parse_mode(&Value::from(755)).is_err());
+}
+
+fn parse_mode(mode: &Value) -> Result<u32, TaskError> {
+    let mode = mode.as_str().ok_or(TaskError::new("modes must be given as octal strings"))?;
+
+    u32::from_str_radix(mode, 8).map_err(|_| TaskError::new(&*format!("malformed mode \"{}\"", mode)))
+}
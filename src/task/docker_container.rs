@@ -0,0 +1,242 @@
+use crate::service::{Service, Request, Response};
+use serde_json::{json, Value};
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+
+/// Manages a docker container through the Docker Engine API.
+///
+/// The container is recreated if the desired configuration is not contained in the configuration of the existing container.
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let name = config["name"].as_str().ok_or(TaskError::new("error reading container name"))?;
+    let socket = config["socket"].as_str().unwrap_or("/var/run/docker.sock");
+    let state = config["state"].as_str().unwrap_or("started");
+
+    if !["started", "stopped", "present", "absent"].contains(&state) {
+        return Err(TaskError::new(&*format!("unknown state \"{}\" given", state)).into());
+    }
+
+    let container = inspect_container(context, socket, name).await?;
+    let mut changed = false;
+
+    if state == "absent" {
+        return match container {
+            Some(_) => {
+                remove_container(context, socket, name).await?;
+                Ok(TaskOutcome::changed())
+            },
+            None => Ok(TaskOutcome::ok())
+        };
+    }
+
+    let image = config["image"].as_str().ok_or(TaskError::new("error reading image"))?;
+    let mut desired = match &config["config"] {
+        Value::Null => json!({}),
+        Value::Object(map) => Value::Object(map.clone()),
+        _ => return Err(TaskError::new("container config must be an object").into())
+    };
+
+    desired["Image"] = image.into();
+
+    let running = match container {
+        Some(container) if is_contained(&desired, &actual_config(&container)) => container["State"]["Running"].as_bool().unwrap_or(false),
+        existing => {
+            if existing.is_some() {
+                remove_container(context, socket, name).await?;
+            }
+
+            if get(context, socket, &*format!("/images/{}/json", image)).await?.is_none() {
+                pull_image(context, socket, image).await?;
+            }
+
+            request(context, socket, "POST", &*format!("/containers/create?name={}", name), Some(desired)).await?;
+            changed = true;
+            false
+        }
+    };
+
+    match (state, running) {
+        ("started", false) => {
+            request(context, socket, "POST", &*format!("/containers/{}/start", name), None).await?;
+            changed = true;
+        },
+        ("stopped", true) => {
+            request(context, socket, "POST", &*format!("/containers/{}/stop", name), None).await?;
+            changed = true;
+        },
+        _ => {}
+    }
+
+    Ok(TaskOutcome::from_changed(changed))
+}
+
+/// Sends a request to the Docker Engine API and returns the body of the response.
+///
+/// In check mode, only `GET` requests are sent; all other requests are assumed to succeed.
+async fn request(context: &mut Box<dyn Service>, socket: &str, method: &str, path: &str, body: Option<Value>) -> Result<Value, Box<dyn std::error::Error>> {
+    if method != "GET" && context.check_mode() {
+        return Ok(Value::Null);
+    }
+
+    let response = send(context, socket, method, path, body).await?;
+
+    check_status(method, path, response.status, &response.body)?;
+    Ok(parse_body(&response.body))
+}
+
+/// Sends a `GET` request to the Docker Engine API; a "not found" response is returned as `None`.
+async fn get(context: &mut Box<dyn Service>, socket: &str, path: &str) -> Result<Option<Value>, Box<dyn std::error::Error>> {
+    let response = send(context, socket, "GET", path, None).await?;
+
+    match response.status {
+        404 => Ok(None),
+        status => {
+            check_status("GET", path, status, &response.body)?;
+            Ok(Some(parse_body(&response.body)))
+        }
+    }
+}
+
+/// Pulls an image; the progress of the pull is streamed in the body of the response, which reports failures as entries with an error.
+///
+/// The tag is always given, as the Docker Engine API pulls all tags of a repository otherwise.
+async fn pull_image(context: &mut Box<dyn Service>, socket: &str, image: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if context.check_mode() {
+        return Ok(());
+    }
+
+    let (repository, tag) = split_image(image);
+    let path = format!("/images/create?fromImage={}&tag={}", encode_query_value(repository), encode_query_value(tag));
+    let response = send(context, socket, "POST", &*path, None).await?;
+
+    check_status("POST", &*path, response.status, &response.body)?;
+    Ok(check_pull_progress(image, &response.body)?)
+}
+
+#[test]
+fn function_split_image() {
+    assert_eq!(split_image("nginx"), ("nginx", "latest"));
+    assert_eq!(split_image("nginx:1.25"), ("nginx", "1.25"));
+    assert_eq!(split_image("registry.local:5000/team/app"), ("registry.local:5000/team/app", "latest"));
+    assert_eq!(split_image("registry.local:5000/team/app:v2"), ("registry.local:5000/team/app", "v2"));
+    assert_eq!(split_image("nginx@sha256:abc"), ("nginx", "sha256:abc"));
+}
+
+/// Splits an image reference `repository[:tag|@digest]` into the repository and the tag or digest, which defaults to `latest`.
+fn split_image(image: &str) -> (&str, &str) {
+    if let Some((repository, digest)) = image.split_once('@') {
+        return (repository, digest);
+    }
+
+    match image.rsplit_once(':') {
+        Some((repository, tag)) if !tag.contains('/') => (repository, tag),
+        _ => (image, "latest")
+    }
+}
+
+#[test]
+fn function_encode_query_value() {
+    assert_eq!(encode_query_value("registry.local:5000/app"), "registry.local%3A5000%2Fapp");
+    assert_eq!(encode_query_value("a b&c=d"), "a%20b%26c%3Dd");
+    assert_eq!(encode_query_value("v1.2_3-x~"), "v1.2_3-x~");
+}
+
+fn encode_query_value(value: &str) -> String {
+    value.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+        byte => format!("%{:02X}", byte)
+    }).collect()
+}
+
+async fn send(context: &mut Box<dyn Service>, socket: &str, method: &str, path: &str, body: Option<Value>) -> Result<Response, Box<dyn std::error::Error>> {
+    let (headers, body) = match body {
+        Some(body) => (vec![("Content-Type".to_string(), "application/json".to_string())], Some(serde_json::to_vec(&body)?)),
+        None => (Vec::new(), None)
+    };
+
+    context.socket_request(socket.to_string(), Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        headers,
+        body
+    }).await
+}
+
+fn parse_body(body: &[u8]) -> Value {
+    serde_json::from_slice(body).unwrap_or(Value::Null)
+}
+
+fn check_status(method: &str, path: &str, status: u16, body: &[u8]) -> Result<(), TaskError> {
+    match status {
+        200..=299 | 304 => Ok(()),
+        status => Err(TaskError::new(&*format!("docker request \"{} {}\" failed with status {}: {}", method, path, status, parse_body(body)["message"].as_str().unwrap_or(""))))
+    }
+}
+
+#[test]
+fn function_check_pull_progress() {
+    assert!(check_pull_progress("nginx", b"{\"status\":\"Pulling from library/nginx\"}\r\n{\"status\":\"Status: Downloaded newer image\"}\r\n").is_ok());
+    assert!(check_pull_progress("nginx", b"").is_ok());
+
+    let err = check_pull_progress("nginx:bad", b"{\"status\":\"Pulling\"}\n{\"errorDetail\":{\"message\":\"manifest unknown\"},\"error\":\"manifest unknown\"}\n").unwrap_err();
+
+    assert_eq!(err.to_string(), "error pulling image \"nginx:bad\": manifest unknown");
+    assert!(check_pull_progress("nginx", b"{\"errorDetail\":{\"message\":\"denied\"}}").is_err());
+    assert!(check_pull_progress("nginx", b"{\"status\":").is_err());
+}
+
+/// Checks the stream of progress entries of an image pull for an error.
+fn check_pull_progress(image: &str, body: &[u8]) -> Result<(), TaskError> {
+    for entry in serde_json::Deserializer::from_slice(body).into_iter::<Value>() {
+        let entry = entry.map_err(|err| TaskError::new(&*format!("error reading progress of pulling image \"{}\": {}", image, err)))?;
+
+        if !entry["error"].is_null() || !entry["errorDetail"].is_null() {
+            let message = entry["error"].as_str().or_else(|| entry["errorDetail"]["message"].as_str()).unwrap_or("unknown error");
+
+            return Err(TaskError::new(&*format!("error pulling image \"{}\": {}", image, message)));
+        }
+    }
+
+    Ok(())
+}
+
+async fn inspect_container(context: &mut Box<dyn Service>, socket: &str, name: &str) -> Result<Option<Value>, Box<dyn std::error::Error>> {
+    get(context, socket, &*format!("/containers/{}/json", name)).await
+}
+
+async fn remove_container(context: &mut Box<dyn Service>, socket: &str, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    request(context, socket, "DELETE", &*format!("/containers/{}?force=true", name), None).await?;
+    Ok(())
+}
+
+/// Maps the result of a container inspection to the structure of a container creation request.
+fn actual_config(container: &Value) -> Value {
+    let mut config = container["Config"].clone();
+
+    config["HostConfig"] = container["HostConfig"].clone();
+    config
+}
+
+#[test]
+fn function_is_contained() {
+    let actual = json!({"Image": "nginx", "Env": ["PATH=/bin", "A=1"], "HostConfig": {"Privileged": false, "Binds": null}});
+
+    assert!(is_contained(&json!({"Image": "nginx"}), &actual));
+    assert!(is_contained(&json!({"Env": ["A=1"], "HostConfig": {"Privileged": false}}), &actual));
+    assert!(!is_contained(&json!({"Image": "httpd"}), &actual));
+    assert!(!is_contained(&json!({"Env": ["A=2"]}), &actual));
+    assert!(!is_contained(&json!({"HostConfig": {"Binds": ["/a:/a"]}}), &actual));
+}
+
+/// Checks whether all entries of the desired value are contained in the actual value.
+///
+/// Objects are compared key by key and arrays entry by entry, ignoring additional entries of the actual value.
+fn is_contained(desired: &Value, actual: &Value) -> bool {
+    match (desired, actual) {
+        (Value::Object(desired), Value::Object(actual)) => desired.iter().all(|(key, value)| match actual.get(key) {
+            Some(actual_value) => is_contained(value, actual_value),
+            None => value.is_null()
+        }),
+        (Value::Array(desired), Value::Array(actual)) => desired.iter().all(|value| actual.iter().any(|actual_value| is_contained(value, actual_value))),
+        (desired, actual) => desired == actual
+    }
+}
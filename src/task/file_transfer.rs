@@ -1,19 +1,44 @@
 use crate::Service;
 use serde_json::Value;
 use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use super::directory_sync;
 use tokio::fs::{write, read};
 
-pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
     let local_path = config["localPath"].as_str().ok_or(TaskError::new("error reading local path"))?;
     let context_path = config["contextPath"].as_str().ok_or(TaskError::new("error reading context path"))?;
 
     match config["direction"].as_str() {
         Some("contextToLocal") => {
-            Ok(write(local_path.to_string(), context.file_read(context_path.to_string()).await?).await?)
+            let data = context.file_read(context_path.to_string()).await?;
+
+            match read(local_path.to_string()).await {
+                Ok(existing) if existing == data => Ok(TaskOutcome::ok()),
+                _ => {
+                    if !context.check_mode() {
+                        write(local_path.to_string(), data).await?;
+                    }
+
+                    Ok(TaskOutcome::changed())
+                }
+            }
         },
         Some("localToContext") => {
-            Ok(context.file_write(context_path.to_string(), read(local_path.to_string()).await?).await?)
+            let data = read(local_path.to_string()).await?;
+
+            match context.file_read(context_path.to_string()).await {
+                Ok(existing) if existing == data => Ok(TaskOutcome::ok()),
+                _ => {
+                    if !context.check_mode() {
+                        context.file_write(context_path.to_string(), data).await?;
+                    }
+
+                    Ok(TaskOutcome::changed())
+                }
+            }
         },
+        Some("directorySync") => directory_sync::run(context, local_path, context_path, config).await,
         Some(dir) => Err(TaskError::new(&*format!("unknown direction \"{}\" given", dir)).into()),
         None => Err(TaskError::new("no direction given").into())
     }
@@ -0,0 +1,89 @@
+use crate::Service;
+use serde_json::Value;
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use super::shell::quote;
+
+pub struct GroupEntry {
+    pub name: String,
+    pub gid: u32,
+    pub members: Vec<String>,
+}
+
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let name = config["name"].as_str().ok_or(TaskError::new("error reading group name"))?;
+    let gid = config["gid"].as_u64();
+    let groups = parse_group_file(&*String::from_utf8(context.file_read("/etc/group".to_string()).await?)?);
+    let existing = groups.iter().find(|group| group.name == name);
+
+    match config["state"].as_str() {
+        Some("present") | None => match existing {
+            Some(group) => match gid {
+                Some(gid) if gid != group.gid as u64 => {
+                    if !context.check_mode() {
+                        context.run(format!("groupmod -g {} {}", gid, quote(name))).await?;
+                    }
+
+                    Ok(TaskOutcome::changed())
+                },
+                _ => Ok(TaskOutcome::ok())
+            },
+            None => {
+                let mut command = String::from("groupadd");
+
+                if let Some(gid) = gid {
+                    command += &*format!(" -g {}", gid);
+                }
+
+                if config["system"].as_bool().unwrap_or(false) {
+                    command += " -r";
+                }
+
+                if !context.check_mode() {
+                    context.run(format!("{} {}", command, quote(name))).await?;
+                }
+
+                Ok(TaskOutcome::changed())
+            }
+        },
+        Some("absent") => match existing {
+            Some(_) => {
+                if !context.check_mode() {
+                    context.run(format!("groupdel {}", quote(name))).await?;
+                }
+
+                Ok(TaskOutcome::changed())
+            },
+            None => Ok(TaskOutcome::ok())
+        },
+        Some(state) => Err(TaskError::new(&*format!("unknown state \"{}\" given", state)).into())
+    }
+}
+
+#[test]
+fn function_parse_group_file() {
+    let groups = parse_group_file("root:x:0:\nwheel:x:10:alice,bob\n\n+\n-nisgroup\nbroken:x:abc:\n+:::\n");
+
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].members.len(), 0);
+    assert_eq!(groups[1].name, "wheel");
+    assert_eq!(groups[1].gid, 10);
+    assert_eq!(groups[1].members, vec!["alice", "bob"]);
+}
+
+/// Parses the content of `/etc/group`, skipping lines that are not regular entries such as NIS `+`/`-` lines.
+pub fn parse_group_file(content: &str) -> Vec<GroupEntry> {
+    content.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+
+        if fields.len() != 4 {
+            return None;
+        }
+
+        Some(GroupEntry {
+            name: fields[0].into(),
+            gid: fields[2].parse().ok()?,
+            members: fields[3].split(',').filter(|member| !member.is_empty()).map(|member| member.into()).collect(),
+        })
+    }).collect()
+}
@@ -0,0 +1,112 @@
+use crate::Service;
+use crate::service::is_not_found;
+use regex::Regex;
+use serde_json::Value;
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let path = config["path"].as_str().ok_or(TaskError::new("error reading path"))?;
+    let regexp = match config["regexp"].as_str() {
+        Some(regexp) => Some(Regex::new(regexp)?),
+        None => None
+    };
+    let line = config["line"].as_str();
+    let present = match config["state"].as_str() {
+        Some("present") | None => true,
+        Some("absent") => false,
+        Some(state) => return Err(TaskError::new(&*format!("unknown state \"{}\" given", state)).into())
+    };
+
+    if regexp.is_none() && line.is_none() {
+        return Err(TaskError::new("neither regexp nor line given").into());
+    }
+
+    let content = match context.file_read(path.to_string()).await {
+        Ok(data) => String::from_utf8(data)?,
+        Err(err) if config["create"].as_bool().unwrap_or(false) && is_not_found(&*err) => String::new(),
+        Err(err) => return Err(err)
+    };
+    let new_content = match present {
+        true => ensure_line_present(&content, regexp.as_ref(), line.ok_or(TaskError::new("error reading line"))?),
+        false => ensure_line_absent(&content, regexp.as_ref(), line)
+    };
+
+    match new_content == content {
+        true => Ok(TaskOutcome::ok()),
+        false => {
+            if !context.check_mode() {
+                context.file_write(path.to_string(), new_content.into_bytes()).await?;
+            }
+
+            Ok(TaskOutcome::changed())
+        }
+    }
+}
+
+#[test]
+fn function_ensure_line_present() {
+    let regexp = Regex::new("^port ").unwrap();
+
+    assert_eq!(ensure_line_present("a\nb\n", None, "c"), "a\nb\nc\n");
+    assert_eq!(ensure_line_present("a\nc\nb\n", None, "c"), "a\nc\nb\n");
+    assert_eq!(ensure_line_present("a\nc", None, "c"), "a\nc");
+    assert_eq!(ensure_line_present("", None, "c"), "c\n");
+    assert_eq!(ensure_line_present("a\nport 22\nb", Some(&regexp), "port 2222"), "a\nport 2222\nb\n");
+    assert_eq!(ensure_line_present("a\nb\n", Some(&regexp), "port 2222"), "a\nb\nport 2222\n");
+
+    let regexp = Regex::new("^PermitRootLogin yes").unwrap();
+    let first = ensure_line_present("a\nPermitRootLogin yes\n", Some(&regexp), "PermitRootLogin no");
+    let second = ensure_line_present(&first, Some(&regexp), "PermitRootLogin no");
+
+    assert_eq!(first, "a\nPermitRootLogin no\n");
+    assert_eq!(second, first);
+    assert_eq!(ensure_line_present("a\nPermitRootLogin no\nb\n", Some(&regexp), "PermitRootLogin no"), "a\nPermitRootLogin no\nb\n");
+}
+
+/// Replaces the last line matching the regular expression or, if no line matches and the line is not present yet, appends the line.
+fn ensure_line_present(content: &str, regexp: Option<&Regex>, line: &str) -> String {
+    let mut lines: Vec<&str> = content.lines().collect();
+    let position = match regexp {
+        Some(regexp) => lines.iter().rposition(|l| regexp.is_match(l)).or_else(|| lines.iter().rposition(|l| *l == line)),
+        None => lines.iter().rposition(|l| *l == line)
+    };
+
+    match position {
+        Some(position) if lines[position] == line => return content.to_string(),
+        Some(position) => lines[position] = line,
+        None => lines.push(line)
+    }
+
+    join_lines(&lines)
+}
+
+#[test]
+fn function_ensure_line_absent() {
+    let regexp = Regex::new("^#").unwrap();
+
+    assert_eq!(ensure_line_absent("a\nb\na\n", None, Some("a")), "b\n");
+    assert_eq!(ensure_line_absent("# x\nb\n# y\n", Some(&regexp), None), "b\n");
+    assert_eq!(ensure_line_absent("b\n", Some(&regexp), None), "b\n");
+}
+
+/// Removes all lines matching the regular expression or, if no regular expression is given, all lines equal to the line.
+fn ensure_line_absent(content: &str, regexp: Option<&Regex>, line: Option<&str>) -> String {
+    let lines: Vec<&str> = content.lines().filter(|l| match (regexp, line) {
+        (Some(regexp), _) => !regexp.is_match(l),
+        (None, Some(line)) => l != &line,
+        (None, None) => true
+    }).collect();
+
+    match lines.len() == content.lines().count() {
+        true => content.to_string(),
+        false => join_lines(&lines)
+    }
+}
+
+pub fn join_lines(lines: &[&str]) -> String {
+    match lines.is_empty() {
+        true => String::new(),
+        false => lines.join("\n") + "\n"
+    }
+}
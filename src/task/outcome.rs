@@ -0,0 +1,182 @@
+use crate::service::{CommandError, CommandOutput};
+use serde_json::{json, Value};
+use std::fmt::{Display, Formatter, Result};
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Status {
+    Ok,
+    Changed,
+    Skipped,
+    Failed,
+    /// A failure that is ignored as requested by the task.
+    Ignored,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::Changed => write!(f, "changed"),
+            Status::Skipped => write!(f, "skipped"),
+            Status::Failed => write!(f, "failed"),
+            Status::Ignored => write!(f, "ignored"),
+        }
+    }
+}
+
+/// The outcome of a task; the duration is set by the caller running the task.
+#[derive(Debug, Clone)]
+pub struct TaskOutcome {
+    pub status: Status,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
+    pub message: String,
+    pub exit_code: Option<i32>,
+}
+
+impl TaskOutcome {
+    pub fn new(status: Status) -> Self {
+        TaskOutcome {
+            status,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration: Duration::default(),
+            message: String::new(),
+            exit_code: None,
+        }
+    }
+
+    pub fn ok() -> Self {
+        Self::new(Status::Ok)
+    }
+
+    pub fn changed() -> Self {
+        Self::new(Status::Changed)
+    }
+
+    pub fn from_changed(changed: bool) -> Self {
+        match changed {
+            true => Self::changed(),
+            false => Self::ok()
+        }
+    }
+
+    pub fn skipped(message: &str) -> Self {
+        Self::new(Status::Skipped).with_message(message)
+    }
+
+    pub fn failed(message: &str) -> Self {
+        Self::new(Status::Failed).with_message(message)
+    }
+
+    /// Creates a failed outcome from an error, keeping the output of a failed command.
+    pub fn from_error(err: &(dyn std::error::Error + 'static)) -> Self {
+        match err.downcast_ref::<CommandError>() {
+            Some(command_error) => Self::failed(&*err.to_string()).with_output(command_error.output.clone()),
+            None => Self::failed(&*err.to_string())
+        }
+    }
+
+    pub fn with_message(mut self, message: &str) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    pub fn with_output(mut self, output: CommandOutput) -> Self {
+        self.stdout = output.stdout;
+        self.stderr = output.stderr;
+        self.exit_code = Some(output.exit_code);
+        self
+    }
+
+    /// Converts the outcome into a value to be used in conditions.
+    pub fn to_value(&self) -> Value {
+        json!({
+            "status": self.status.to_string(),
+            "changed": self.status == Status::Changed,
+            "failed": self.status == Status::Failed || self.status == Status::Ignored,
+            "skipped": self.status == Status::Skipped,
+            "stdout": self.stdout,
+            "stderr": self.stderr,
+            "message": self.message,
+            "exitCode": self.exit_code,
+        })
+    }
+}
+
+pub struct TaskResult {
+    pub title: String,
+    pub task_type: String,
+    pub outcome: TaskOutcome,
+}
+
+/// The results of the tasks processed for a host; `error` is set if the host could not be processed at all.
+pub struct HostOutcome {
+    pub title: String,
+    pub tasks: Vec<TaskResult>,
+    pub error: Option<String>,
+    /// Whether the host was processed; hosts are left out once too many hosts failed.
+    pub processed: bool,
+}
+
+#[test]
+fn function_recap() {
+    let mut host = HostOutcome::new("web1");
+
+    host.tasks.push(TaskResult { title: "a".into(), task_type: "command".into(), outcome: TaskOutcome::changed() });
+    host.tasks.push(TaskResult { title: "b".into(), task_type: "command".into(), outcome: TaskOutcome::ok() });
+    host.tasks.push(TaskResult { title: "c".into(), task_type: "command".into(), outcome: TaskOutcome::failed("boom") });
+
+    assert_eq!(host.recap(), "web1: ok=1 changed=1 skipped=0 failed=1 ignored=0 (task \"c\" (command) failed: boom)");
+    assert!(host.is_failed());
+    assert_eq!(host.failure_message().unwrap(), "task \"c\" (command) failed: boom");
+    assert_eq!(HostOutcome::not_processed("web2").recap(), "web2: not processed");
+}
+
+impl HostOutcome {
+    pub fn new(title: &str) -> Self {
+        HostOutcome {
+            title: title.into(),
+            tasks: Vec::new(),
+            error: None,
+            processed: true,
+        }
+    }
+
+    pub fn not_processed(title: &str) -> Self {
+        HostOutcome { processed: false, ..HostOutcome::new(title) }
+    }
+
+    pub fn count(&self, status: Status) -> usize {
+        self.tasks.iter().filter(|task| task.outcome.status == status).count()
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some() || self.count(Status::Failed) > 0
+    }
+
+    /// Returns the error of the host or, if there is none, the message of the first failed task.
+    pub fn failure_message(&self) -> Option<String> {
+        match &self.error {
+            Some(error) => Some(error.clone()),
+            None => self.tasks.iter().find(|task| task.outcome.status == Status::Failed)
+                .map(|task| format!("task \"{}\" ({}) failed: {}", task.title, task.task_type, task.outcome.message))
+        }
+    }
+
+    pub fn recap(&self) -> String {
+        if !self.processed {
+            return format!("{}: not processed", self.title);
+        }
+
+        let mut recap = format!("{}: ok={} changed={} skipped={} failed={} ignored={}", self.title, self.count(Status::Ok), self.count(Status::Changed), self.count(Status::Skipped), self.count(Status::Failed), self.count(Status::Ignored));
+
+        if let Some(message) = self.failure_message() {
+            recap += &*format!(" ({})", message);
+        }
+
+        recap
+    }
+}
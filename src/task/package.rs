@@ -0,0 +1,156 @@
+use crate::Service;
+use serde_json::Value;
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use super::shell::{quote, succeeds};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum PackageManager {
+    Apt,
+    Dnf,
+    Pacman,
+    Apk,
+}
+
+#[derive(PartialEq)]
+enum State {
+    Present,
+    Absent,
+    Latest,
+}
+
+#[test]
+fn function_package_manager_command() {
+    assert_eq!(PackageManager::from_name("apt-get").unwrap(), PackageManager::Apt);
+    assert!(PackageManager::from_name("yum").is_err());
+    assert_eq!(PackageManager::Pacman.command(&State::Present, &["nginx", "git"]), "pacman -S --noconfirm --needed 'nginx' 'git'");
+    assert_eq!(PackageManager::Apk.command(&State::Absent, &["nginx"]), "apk del 'nginx'");
+    assert_eq!(PackageManager::Dnf.command(&State::Latest, &["nginx"]), "dnf upgrade -y 'nginx'");
+    assert_eq!(PackageManager::Dnf.is_upgradable("nginx"), "dnf -q check-update 'nginx'; test $? -eq 100");
+}
+
+impl PackageManager {
+    fn from_name(name: &str) -> Result<Self, TaskError> {
+        match name {
+            "apt" | "apt-get" => Ok(PackageManager::Apt),
+            "dnf" => Ok(PackageManager::Dnf),
+            "pacman" => Ok(PackageManager::Pacman),
+            "apk" => Ok(PackageManager::Apk),
+            _ => Err(TaskError::new(&*format!("unknown package manager \"{}\"", name)))
+        }
+    }
+
+    fn is_installed(&self, package: &str) -> String {
+        match self {
+            PackageManager::Apt => format!("dpkg-query -W -f='${{Status}}' {} 2>/dev/null | grep -q 'ok installed'", quote(package)),
+            PackageManager::Dnf => format!("rpm -q {}", quote(package)),
+            PackageManager::Pacman => format!("pacman -Q {}", quote(package)),
+            PackageManager::Apk => format!("apk info -e {}", quote(package)),
+        }
+    }
+
+    fn is_upgradable(&self, package: &str) -> String {
+        match self {
+            PackageManager::Apt => format!("apt list --upgradable 2>/dev/null | grep -q ^{}/", quote(package)),
+            PackageManager::Dnf => format!("dnf -q check-update {}; test $? -eq 100", quote(package)),
+            PackageManager::Pacman => format!("pacman -Qu {}", quote(package)),
+            PackageManager::Apk => format!("apk version {} 2>/dev/null | grep -q '<'", quote(package)),
+        }
+    }
+
+    fn update_cache(&self) -> &'static str {
+        match self {
+            PackageManager::Apt => "apt-get update",
+            PackageManager::Dnf => "dnf makecache",
+            PackageManager::Pacman => "pacman -Sy",
+            PackageManager::Apk => "apk update",
+        }
+    }
+
+    fn command(&self, state: &State, packages: &[&str]) -> String {
+        let command = match (self, state) {
+            (PackageManager::Apt, State::Absent) => "DEBIAN_FRONTEND=noninteractive apt-get remove -y",
+            (PackageManager::Apt, _) => "DEBIAN_FRONTEND=noninteractive apt-get install -y",
+            (PackageManager::Dnf, State::Present) => "dnf install -y",
+            (PackageManager::Dnf, State::Absent) => "dnf remove -y",
+            (PackageManager::Dnf, State::Latest) => "dnf upgrade -y",
+            (PackageManager::Pacman, State::Present) => "pacman -S --noconfirm --needed",
+            (PackageManager::Pacman, State::Absent) => "pacman -R --noconfirm",
+            (PackageManager::Pacman, State::Latest) => "pacman -S --noconfirm",
+            (PackageManager::Apk, State::Present) => "apk add",
+            (PackageManager::Apk, State::Absent) => "apk del",
+            (PackageManager::Apk, State::Latest) => "apk add --upgrade",
+        };
+        let packages: Vec<String> = packages.iter().map(|package| quote(package)).collect();
+
+        format!("{} {}", command, packages.join(" "))
+    }
+}
+
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let packages = get_packages(config)?;
+    let state = match config["state"].as_str() {
+        Some("present") | None => State::Present,
+        Some("absent") => State::Absent,
+        Some("latest") => State::Latest,
+        Some(state) => return Err(TaskError::new(&*format!("unknown state \"{}\" given", state)).into())
+    };
+    let manager = match config["manager"].as_str() {
+        Some(name) => PackageManager::from_name(name)?,
+        None => detect_package_manager(context).await?
+    };
+
+    if config["updateCache"].as_bool().unwrap_or(false) && !context.check_mode() {
+        context.run(manager.update_cache().to_string()).await?;
+    }
+
+    // packages to install are kept apart from installed packages to upgrade, as e.g. `dnf upgrade` fails for packages that are not installed
+    let mut missing = Vec::new();
+    let mut pending = Vec::new();
+
+    for package in packages {
+        let installed = succeeds(context, &*manager.is_installed(package)).await?;
+
+        match (&state, installed) {
+            (State::Present, false) | (State::Latest, false) => missing.push(package),
+            (State::Absent, true) => pending.push(package),
+            (State::Latest, true) if succeeds(context, &*manager.is_upgradable(package)).await? => pending.push(package),
+            _ => {}
+        }
+    }
+
+    match missing.is_empty() && pending.is_empty() {
+        true => Ok(TaskOutcome::ok()),
+        false => {
+            if !context.check_mode() {
+                if !missing.is_empty() {
+                    context.run(manager.command(&State::Present, &missing)).await?;
+                }
+
+                if !pending.is_empty() {
+                    context.run(manager.command(&state, &pending)).await?;
+                }
+            }
+
+            Ok(TaskOutcome::changed())
+        }
+    }
+}
+
+fn get_packages(config: &Value) -> Result<Vec<&str>, TaskError> {
+    match (config["name"].as_str(), config["names"].as_array()) {
+        (Some(name), None) => Ok(vec![name]),
+        (None, Some(names)) => names.iter().map(|name| name.as_str().ok_or(TaskError::new("error reading package name"))).collect(),
+        (Some(_), Some(_)) => Err(TaskError::new("only one of name and names may be given")),
+        (None, None) => Err(TaskError::new("no package name given"))
+    }
+}
+
+async fn detect_package_manager(context: &mut Box<dyn Service>) -> Result<PackageManager, Box<dyn std::error::Error>> {
+    let output = context.query("for pm in apt-get dnf pacman apk; do if command -v $pm >/dev/null 2>&1; then echo $pm; break; fi; done".to_string()).await?;
+
+    match output.stdout.trim() {
+        "" => Err(TaskError::new("no supported package manager found").into()),
+        name => Ok(PackageManager::from_name(name)?)
+    }
+}
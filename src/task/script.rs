@@ -0,0 +1,45 @@
+use crate::service::{Service, CommandOutput};
+use serde_json::Value;
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use super::shell::{create_temp_file, quote};
+use tokio::fs::read;
+
+/// Uploads a local script to a temporary path on the context, runs it and removes it again.
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let local_path = config["localPath"].as_str().ok_or(TaskError::new("error reading local path"))?;
+    let args = match &config["args"] {
+        Value::Null => Vec::new(),
+        Value::Array(args) => args.iter().map(|arg| arg.as_str().map(quote).ok_or(TaskError::new("error reading argument"))).collect::<Result<Vec<String>, TaskError>>()?,
+        _ => return Err(TaskError::new("args must be an array").into())
+    };
+    let data = read(local_path).await?;
+
+    if context.check_mode() {
+        return Ok(TaskOutcome::changed());
+    }
+
+    let path = create_temp_file(context, "script").await?;
+    let res = run_script(context, &path, data, config["interpreter"].as_str(), &args).await;
+
+    context.file_remove(path).await?;
+
+    Ok(TaskOutcome::changed().with_output(res?))
+}
+
+async fn run_script(context: &mut Box<dyn Service>, path: &str, data: Vec<u8>, interpreter: Option<&str>, args: &[String]) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+    context.file_write(path.to_string(), data).await?;
+    context.file_set_mode(path.to_string(), 0o700).await?;
+
+    let mut command = match interpreter {
+        Some(interpreter) => format!("{} {}", interpreter, quote(path)),
+        None => quote(path)
+    };
+
+    for arg in args {
+        command += " ";
+        command += arg;
+    }
+
+    context.run(command).await
+}
@@ -0,0 +1,36 @@
+use crate::service::{CommandError, Service};
+use super::error::TaskError;
+
+#[test]
+fn function_quote() {
+    assert_eq!(quote("nginx"), "'nginx'");
+    assert_eq!(quote("it's"), "'it'\\''s'");
+    assert_eq!(quote(""), "''");
+}
+
+/// Quotes an argument for use in a POSIX shell command line.
+pub fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Runs a probe on the context and reports whether it exited with status zero; the output of the probe is discarded.
+pub async fn succeeds(context: &mut Box<dyn Service>, condition: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    match context.query(format!("( {} ) >/dev/null 2>&1", condition)).await {
+        Ok(_) => Ok(true),
+        Err(err) if err.is::<CommandError>() => Ok(false),
+        Err(err) => Err(err)
+    }
+}
+
+/// Creates an empty temporary file with an unpredictable name on the context and returns its path; the file is removed after the task even if the task is cancelled before it removes the file itself.
+pub async fn create_temp_file(context: &mut Box<dyn Service>, name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let path = context.run(format!("mktemp {}", quote(&*format!("/tmp/infco-{}.XXXXXXXX", name)))).await?.stdout.trim().to_string();
+
+    match path.is_empty() {
+        true => Err(TaskError::new("error creating temporary file").into()),
+        false => {
+            context.add_temp_file(path.clone());
+            Ok(path)
+        }
+    }
+}
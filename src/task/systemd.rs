@@ -0,0 +1,84 @@
+use crate::Service;
+use serde_json::Value;
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use super::shell::{quote, succeeds};
+
+/// Manages a systemd unit.
+///
+/// Running `systemctl daemon-reload` does not change the state of the unit and is, therefore, not reported as a change.
+/// A unit counts as enabled in every state for which `systemctl is-enabled` succeeds, which includes e.g. `static` and `indirect` units.
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let mut changed = false;
+
+    if config["daemonReload"].as_bool().unwrap_or(false) && !context.check_mode() {
+        context.run("systemctl daemon-reload".to_string()).await?;
+    }
+
+    let unit = match config["unit"].as_str() {
+        Some(unit) => quote(unit),
+        None if config["state"].is_null() && config["enabled"].is_null() => return Ok(TaskOutcome::ok()),
+        None => return Err(TaskError::new("error reading unit").into())
+    };
+
+    if let Some(enabled) = config["enabled"].as_bool() {
+        let is_enabled = succeeds(context, &*format!("systemctl is-enabled --quiet {}", unit)).await?;
+        let command = match (enabled, is_enabled) {
+            (true, false) => Some(format!("systemctl enable {}", unit)),
+            (false, true) => get_disable_command(context.query(format!("systemctl is-enabled {}", unit)).await?.stdout.trim(), &unit),
+            _ => None
+        };
+
+        if let Some(command) = command {
+            if !context.check_mode() {
+                context.run(command).await?;
+            }
+
+            changed = true;
+        }
+    }
+
+    let action = match config["state"].as_str() {
+        Some(state @ "started") | Some(state @ "stopped") => {
+            let is_active = succeeds(context, &*format!("systemctl is-active --quiet {}", unit)).await?;
+
+            match (state, is_active) {
+                ("started", false) => Some("start"),
+                ("stopped", true) => Some("stop"),
+                _ => None
+            }
+        },
+        Some("restarted") => Some("restart"),
+        Some("reloaded") => Some("reload"),
+        Some(state) => return Err(TaskError::new(&*format!("unknown state \"{}\" given", state)).into()),
+        None => None
+    };
+
+    if let Some(action) = action {
+        if !context.check_mode() {
+            context.run(format!("systemctl {} {}", action, unit)).await?;
+        }
+
+        changed = true;
+    }
+
+    Ok(TaskOutcome::from_changed(changed))
+}
+
+#[test]
+fn function_get_disable_command() {
+    assert_eq!(get_disable_command("enabled", "'nginx'"), Some("systemctl disable 'nginx'".into()));
+    assert_eq!(get_disable_command("enabled-runtime", "'nginx'"), Some("systemctl disable --runtime 'nginx'".into()));
+    assert_eq!(get_disable_command("static", "'nginx'"), None);
+    assert_eq!(get_disable_command("indirect", "'nginx'"), None);
+    assert_eq!(get_disable_command("alias", "'nginx'"), None);
+}
+
+/// Returns the command disabling a unit in the given state reported by `systemctl is-enabled`; units that are enabled by other means than their own install section, e.g. `static`, `indirect`, `alias` or `generated` units, cannot be disabled and are left as they are.
+fn get_disable_command(state: &str, unit: &str) -> Option<String> {
+    match state {
+        "enabled" => Some(format!("systemctl disable {}", unit)),
+        "enabled-runtime" => Some(format!("systemctl disable --runtime {}", unit)),
+        _ => None
+    }
+}
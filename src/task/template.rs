@@ -0,0 +1,49 @@
+use crate::Service;
+use crate::templating::renderer;
+use serde_json::Value;
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use tokio::fs::read_to_string;
+
+pub async fn run(context: &mut Box<dyn Service>, config: &Value, host_vars: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let local_path = config["localPath"].as_str().ok_or(TaskError::new("error reading local path"))?;
+    let context_path = config["contextPath"].as_str().ok_or(TaskError::new("error reading context path"))?;
+    let vars = merge_vars(host_vars, &config["vars"]);
+    let content = renderer::render(&*read_to_string(local_path).await?, &vars)
+        .map_err(|e| TaskError::new(&*format!("error rendering template \"{}\": {}", local_path, e)))?;
+
+    match context.file_read(context_path.to_string()).await {
+        Ok(existing) if existing == content.as_bytes() => Ok(TaskOutcome::ok()),
+        _ => {
+            if !context.check_mode() {
+                context.file_write(context_path.to_string(), content.into_bytes()).await?;
+            }
+
+            Ok(TaskOutcome::changed())
+        }
+    }
+}
+
+#[test]
+fn function_merge_vars() {
+    use serde_json::json;
+
+    assert_eq!(merge_vars(&json!({"a": 1, "b": 2}), &json!({"b": 3})), json!({"a": 1, "b": 3}));
+    assert_eq!(merge_vars(&Value::Null, &json!({"b": 3})), json!({"b": 3}));
+    assert_eq!(merge_vars(&json!({"a": 1}), &Value::Null), json!({"a": 1}));
+}
+
+/// Merges the task variables into the host variables; task variables take precedence.
+fn merge_vars(host_vars: &Value, task_vars: &Value) -> Value {
+    let mut vars = serde_json::Map::new();
+
+    for source in &[host_vars, task_vars] {
+        if let Some(map) = source.as_object() {
+            for (key, value) in map {
+                vars.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    Value::Object(vars)
+}
@@ -0,0 +1,106 @@
+use crate::Service;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use super::shell::{create_temp_file, quote, succeeds};
+use tokio::fs::read;
+
+#[derive(Debug, PartialEq)]
+enum Format {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+/// Extracts an archive into a destination directory on the context.
+///
+/// The archive is either uploaded from `localPath` or taken from `contextPath`. The checksum of the extracted archive is stored in a marker file in the destination; if it matches, or if the path given by `creates` exists, the archive is not extracted again.
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let destination = config["destination"].as_str().ok_or(TaskError::new("error reading destination"))?;
+    let marker = config["marker"].as_str().map(String::from).unwrap_or(format!("{}/.infco-unarchive.sha256", destination.trim_end_matches('/')));
+
+    if let Some(creates) = config["creates"].as_str() {
+        if succeeds(context, &*format!("test -e {}", quote(creates))).await? {
+            return Ok(TaskOutcome::ok());
+        }
+    }
+
+    let (archive_name, local_data) = match (config["localPath"].as_str(), config["contextPath"].as_str()) {
+        (Some(local_path), None) => (local_path, Some(read(local_path).await?)),
+        (None, Some(context_path)) => (context_path, None),
+        (Some(_), Some(_)) => return Err(TaskError::new("only one of localPath and contextPath may be given").into()),
+        (None, None) => return Err(TaskError::new("neither localPath nor contextPath given").into())
+    };
+    let format = match config["format"].as_str() {
+        Some(format) => get_format(format)?,
+        None => get_format(archive_name)?
+    };
+    let checksum = match &local_data {
+        Some(data) => format!("{:x}", Sha256::digest(data)),
+        None => context.query(format!("sha256sum {}", quote(archive_name))).await?.stdout.split_whitespace().next().ok_or(TaskError::new("error calculating checksum"))?.to_string()
+    };
+
+    if let Ok(existing) = context.file_read(marker.clone()).await {
+        if String::from_utf8_lossy(&existing).trim() == checksum {
+            return Ok(TaskOutcome::ok());
+        }
+    }
+
+    if context.check_mode() {
+        return Ok(TaskOutcome::changed());
+    }
+
+    match local_data {
+        Some(data) => {
+            let path = create_temp_file(context, "unarchive").await?;
+            let res = upload_and_extract(context, &path, data, &format, destination).await;
+
+            context.file_remove(path).await?;
+            res?;
+        },
+        None => extract(context, archive_name, &format, destination).await?
+    }
+
+    context.file_write(marker, format!("{}\n", checksum).into_bytes()).await?;
+
+    Ok(TaskOutcome::changed())
+}
+
+async fn upload_and_extract(context: &mut Box<dyn Service>, path: &str, data: Vec<u8>, format: &Format, destination: &str) -> Result<(), Box<dyn std::error::Error>> {
+    context.file_write(path.to_string(), data).await?;
+    extract(context, path, format, destination).await
+}
+
+async fn extract(context: &mut Box<dyn Service>, path: &str, format: &Format, destination: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let command = match format {
+        Format::Tar => format!("tar -xf {} -C {}", quote(path), quote(destination)),
+        Format::TarGz => format!("tar -xzf {} -C {}", quote(path), quote(destination)),
+        Format::Zip => format!("unzip -o -q {} -d {}", quote(path), quote(destination)),
+    };
+
+    context.run(format!("mkdir -p {} && {}", quote(destination), command)).await?;
+    Ok(())
+}
+
+#[test]
+fn function_get_format() {
+    assert_eq!(get_format("release.tar.gz").unwrap(), Format::TarGz);
+    assert_eq!(get_format("release.tgz").unwrap(), Format::TarGz);
+    assert_eq!(get_format("release.tar").unwrap(), Format::Tar);
+    assert_eq!(get_format("zip").unwrap(), Format::Zip);
+    assert!(get_format("release.rar").is_err());
+}
+
+/// Determines the archive format from a format name or a file name.
+fn get_format(name: &str) -> Result<Format, TaskError> {
+    match name {
+        "tar" => Ok(Format::Tar),
+        "tar.gz" | "tgz" => Ok(Format::TarGz),
+        "zip" => Ok(Format::Zip),
+        name if name.ends_with(".tar") => Ok(Format::Tar),
+        name if name.ends_with(".tar.gz") || name.ends_with(".tgz") => Ok(Format::TarGz),
+        name if name.ends_with(".zip") => Ok(Format::Zip),
+        name => Err(TaskError::new(&*format!("unknown archive format of \"{}\"", name)))
+    }
+}
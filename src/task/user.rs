@@ -0,0 +1,187 @@
+use crate::Service;
+use serde_json::Value;
+use super::error::TaskError;
+use super::outcome::TaskOutcome;
+use super::group::{parse_group_file, GroupEntry};
+use super::shell::quote;
+
+pub struct UserEntry {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+    pub shell: String,
+}
+
+pub async fn run(context: &mut Box<dyn Service>, config: &Value) -> Result<TaskOutcome, Box<dyn std::error::Error>> {
+    let name = config["name"].as_str().ok_or(TaskError::new("error reading user name"))?;
+    let users = parse_passwd_file(&*String::from_utf8(context.file_read("/etc/passwd".to_string()).await?)?);
+    let groups = parse_group_file(&*String::from_utf8(context.file_read("/etc/group".to_string()).await?)?);
+    let existing = users.into_iter().find(|user| user.name == name);
+
+    match config["state"].as_str() {
+        Some("present") | None => {},
+        Some("absent") => return match existing {
+            Some(_) => {
+                let remove_home = if config["removeHome"].as_bool().unwrap_or(false) { " -r" } else { "" };
+
+                if !context.check_mode() {
+                    context.run(format!("userdel{} {}", remove_home, quote(name))).await?;
+                }
+
+                Ok(TaskOutcome::changed())
+            },
+            None => Ok(TaskOutcome::ok())
+        },
+        Some(state) => return Err(TaskError::new(&*format!("unknown state \"{}\" given", state)).into())
+    }
+
+    let options = get_options(config, existing.as_ref(), &groups)?;
+    let mut changed = false;
+
+    match &existing {
+        Some(_) if options.is_empty() => {},
+        Some(_) => {
+            if !context.check_mode() {
+                context.run(format!("usermod{} {}", options, quote(name))).await?;
+            }
+
+            changed = true;
+        },
+        None => {
+            let system = if config["system"].as_bool().unwrap_or(false) { " -r" } else { "" };
+
+            if !context.check_mode() {
+                context.run(format!("useradd -m{}{} {}", system, options, quote(name))).await?;
+            }
+
+            changed = true;
+        }
+    }
+
+    if existing.is_none() && context.check_mode() {
+        return Ok(TaskOutcome::from_changed(changed));
+    }
+
+    if let Some(keys) = config["authorizedKeys"].as_str() {
+        let home = match (config["home"].as_str(), existing) {
+            (Some(home), _) => home.to_string(),
+            (None, Some(user)) => user.home,
+            (None, None) => {
+                let users = parse_passwd_file(&*String::from_utf8(context.file_read("/etc/passwd".to_string()).await?)?);
+
+                users.into_iter().find(|user| user.name == name).ok_or(TaskError::new(&*format!("user \"{}\" not found after creation", name)))?.home
+            }
+        };
+        let path = format!("{}/.ssh/authorized_keys", home);
+
+        match context.file_read(path.clone()).await {
+            Ok(existing) if existing == keys.as_bytes() => {},
+            _ if context.check_mode() => changed = true,
+            _ => {
+                context.run(format!("install -d -m 700 -o {user} -g $(id -g {user}) {dir}", user = quote(name), dir = quote(&*format!("{}/.ssh", home)))).await?;
+                context.file_write(path.clone(), keys.as_bytes().to_vec()).await?;
+                context.run(format!("chown {user}:$(id -g {user}) {path} && chmod 600 {path}", user = quote(name), path = quote(&*path))).await?;
+                changed = true;
+            }
+        }
+    }
+
+    Ok(TaskOutcome::from_changed(changed))
+}
+
+#[test]
+fn function_get_options() {
+    let groups = parse_group_file("alice:x:1000:\nwheel:x:10:alice\ndocker:x:20:\n");
+    let user = UserEntry { name: "alice".into(), uid: 1000, gid: 1000, home: "/home/alice".into(), shell: "/bin/bash".into() };
+
+    assert_eq!(get_options(&serde_json::json!({"groups": ["wheel", "alice"]}), Some(&user), &groups).unwrap(), "");
+    assert_eq!(get_options(&serde_json::json!({"group": "wheel", "groups": ["wheel"]}), Some(&user), &groups).unwrap(), " -g 'wheel'");
+    assert_eq!(get_options(&serde_json::json!({"groups": ["docker", "wheel"]}), Some(&user), &groups).unwrap(), " -G 'docker,wheel'");
+}
+
+/// Builds the `useradd`/`usermod` options for the attributes that differ from the existing user.
+fn get_options(config: &Value, existing: Option<&UserEntry>, groups: &[GroupEntry]) -> Result<String, TaskError> {
+    let mut options = String::new();
+
+    if let Some(uid) = config["uid"].as_u64() {
+        if existing.map(|user| user.uid as u64) != Some(uid) {
+            options += &*format!(" -u {}", uid);
+        }
+    }
+
+    if let Some(group) = config["group"].as_str() {
+        let gid = groups.iter().find(|entry| entry.name == group).map(|entry| entry.gid);
+
+        if gid.is_none() || existing.map(|user| user.gid) != gid {
+            options += &*format!(" -g {}", quote(group));
+        }
+    }
+
+    if let Some(shell) = config["shell"].as_str() {
+        if existing.map(|user| &*user.shell) != Some(shell) {
+            options += &*format!(" -s {}", quote(shell));
+        }
+    }
+
+    if let Some(home) = config["home"].as_str() {
+        match existing {
+            Some(user) if user.home == home => {},
+            Some(_) => options += &*format!(" -m -d {}", quote(home)),
+            None => options += &*format!(" -d {}", quote(home))
+        }
+    }
+
+    if let Some(desired) = config["groups"].as_array() {
+        let primary = config["group"].as_str()
+            .or_else(|| existing.and_then(|user| groups.iter().find(|group| group.gid == user.gid)).map(|group| &*group.name));
+        let mut desired = desired.iter().map(|group| group.as_str().ok_or(TaskError::new("error reading supplementary group"))).collect::<Result<Vec<&str>, TaskError>>()?;
+        let mut current: Vec<&str> = match existing {
+            Some(user) => groups.iter().filter(|group| group.members.contains(&user.name)).map(|group| &*group.name).collect(),
+            None => Vec::new()
+        };
+
+        // the primary group is implied and not necessarily listed as a member, so it must not cause a difference
+        desired.retain(|group| Some(*group) != primary);
+        current.retain(|group| Some(*group) != primary);
+
+        desired.sort_unstable();
+        current.sort_unstable();
+
+        if desired != current && !(existing.is_none() && desired.is_empty()) {
+            options += &*format!(" -G {}", quote(&*desired.join(",")));
+        }
+    }
+
+    Ok(options)
+}
+
+#[test]
+fn function_parse_passwd_file() {
+    let users = parse_passwd_file("root:x:0:0:root:/root:/bin/bash\nsvc:x:990:990::/srv/svc:/usr/sbin/nologin\n+@admins\n-bob\n+::::::\n");
+
+    assert_eq!(users.len(), 2);
+    assert_eq!(users[1].name, "svc");
+    assert_eq!(users[1].uid, 990);
+    assert_eq!(users[1].home, "/srv/svc");
+    assert_eq!(users[1].shell, "/usr/sbin/nologin");
+}
+
+/// Parses the content of `/etc/passwd`, skipping lines that are not regular entries such as NIS `+`/`-` lines.
+pub fn parse_passwd_file(content: &str) -> Vec<UserEntry> {
+    content.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+
+        if fields.len() != 7 {
+            return None;
+        }
+
+        Some(UserEntry {
+            name: fields[0].into(),
+            uid: fields[2].parse().ok()?,
+            gid: fields[3].parse().ok()?,
+            home: fields[5].into(),
+            shell: fields[6].into(),
+        })
+    }).collect()
+}
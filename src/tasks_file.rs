@@ -0,0 +1,296 @@
+use crate::error::InfcoError;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::fs::{canonicalize, read_to_string};
+use std::path::{Path, PathBuf};
+
+/// A tasks file with its includes and roles resolved.
+///
+/// An entry `{"include": "<path>"}` in a list of tasks is replaced by the tasks of the given file, which has the same format as a tasks file; its handlers are added to the handlers.
+/// The tags of an include or role entry are added to the tags of the tasks it is replaced by.
+/// An entry `{"role": "<name>"}` is replaced by the tasks of the role in `roles/<name>` next to the tasks file:
+/// * `tasks.json`: the list of tasks of the role
+/// * `handlers.json`: the list of handlers of the role (optional)
+/// * `defaults.json`: the default variables of the role, which are overridden by the variables of a host (optional); they only apply to the tasks and handlers of the role and are attached to them as `roleDefaults`
+/// * `templates/` and `files/`: the local files of the role; relative local paths of the tasks of the role refer to these directories
+///
+/// A handler defined more than once, e.g. by a file included twice, is only added once; different handlers with the same title are an error.
+pub struct TasksFile {
+    pub content: Value,
+    pub tasks: Vec<Value>,
+    pub handlers: Vec<Value>,
+}
+
+#[test]
+fn function_load() {
+    let dir = std::env::temp_dir().join(format!("infco-tasks-file-{}", std::process::id()));
+    let write = |path: &str, content: Value| {
+        let path = dir.join(path);
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content.to_string()).unwrap();
+    };
+
+    write("main.json", json!({"tags": ["a"], "tasks": [{"title": "a"}, {"include": "common/b.json"}, {"role": "web"}], "handlers": [{"title": "h1"}]}));
+    write("common/b.json", json!({"tasks": [{"title": "b"}], "handlers": [{"title": "h2"}]}));
+    write("roles/web/tasks.json", json!([{"title": "c", "type": "template", "config": {"localPath": "site.conf"}}, {"include": "extra.json"}]));
+    write("roles/web/extra.json", json!({"tasks": [{"title": "d", "type": "fileTransfer", "config": {"localPath": "/etc/hosts"}}]}));
+    write("roles/web/handlers.json", json!([{"title": "h3"}]));
+    write("roles/web/defaults.json", json!({"port": 80}));
+    write("roles/db/tasks.json", json!([{"title": "e"}]));
+    write("roles/db/defaults.json", json!({"port": 5432}));
+    write("twice.json", json!({"tasks": [{"include": "common/b.json"}, {"include": "common/b.json"}, {"role": "web"}, {"role": "db"}]}));
+    write("conflict.json", json!({"tasks": [{"include": "common/b.json"}], "handlers": [{"title": "h2", "type": "command"}]}));
+    write("cycle.json", json!({"tasks": [{"include": "common/cycle.json"}]}));
+    write("common/cycle.json", json!({"tasks": [{"include": "../cycle.json"}]}));
+
+    let tasks_file = TasksFile::load(dir.join("main.json").to_str().unwrap()).unwrap();
+    let twice = TasksFile::load(dir.join("twice.json").to_str().unwrap()).unwrap();
+    let conflict = TasksFile::load(dir.join("conflict.json").to_str().unwrap());
+    let cycle = TasksFile::load(dir.join("cycle.json").to_str().unwrap());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(tasks_file.tasks.iter().map(|task| task["title"].as_str().unwrap()).collect::<Vec<&str>>(), vec!["a", "b", "c", "d"]);
+    assert_eq!(tasks_file.tasks[2]["config"]["localPath"], dir.join("roles/web/templates/site.conf").to_str().unwrap());
+    assert_eq!(tasks_file.tasks[3]["config"]["localPath"], "/etc/hosts");
+    assert_eq!(tasks_file.handlers.iter().map(|handler| handler["title"].as_str().unwrap()).collect::<Vec<&str>>(), vec!["h1", "h2", "h3"]);
+    assert_eq!(tasks_file.tasks[0]["roleDefaults"], Value::Null);
+    assert_eq!(tasks_file.tasks[2]["roleDefaults"], json!({"port": 80}));
+    assert_eq!(tasks_file.tasks[3]["roleDefaults"], json!({"port": 80}));
+    assert_eq!(tasks_file.handlers[2]["roleDefaults"], json!({"port": 80}));
+    assert_eq!(twice.handlers.iter().map(|handler| handler["title"].as_str().unwrap()).collect::<Vec<&str>>(), vec!["h2", "h3"]);
+    assert_eq!(twice.tasks.iter().map(|task| task["roleDefaults"]["port"].clone()).collect::<Vec<Value>>(), vec![Value::Null, Value::Null, json!(80), json!(80), json!(5432)]);
+    assert!(conflict.err().unwrap().to_string().starts_with("handler \"h2\" is defined more than once"));
+    assert!(cycle.err().unwrap().to_string().starts_with("include cycle: "));
+}
+
+impl TasksFile {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = Path::new(path);
+        let mut loader = Loader {
+            roles_dir: path.parent().unwrap_or_else(|| Path::new("")).join("roles"),
+            stack: Vec::new(),
+            roles: Vec::new(),
+            handlers: Vec::new(),
+            role_defaults: Vec::new(),
+        };
+        let content = loader.enter(path)?;
+        let tasks = loader.expand_tasks_file(&content, path, None)?;
+
+        Ok(TasksFile { content, tasks, handlers: loader.handlers })
+    }
+
+    /// Returns a hash of the resolved tasks, which changes whenever the tasks file or one of its includes or roles changes.
+    pub fn hash(&self) -> String {
+        let resolved = json!({
+            "content": self.content,
+            "tasks": self.tasks,
+            "handlers": self.handlers,
+        });
+
+        format!("{:x}", Sha256::digest(resolved.to_string().as_bytes()))
+    }
+}
+
+struct Loader {
+    roles_dir: PathBuf,
+    stack: Vec<(PathBuf, String)>,
+    roles: Vec<String>,
+    handlers: Vec<Value>,
+    /// The defaults of the roles being expanded, innermost last.
+    role_defaults: Vec<Map<String, Value>>,
+}
+
+impl Loader {
+    /// Reads a file, which must not be part of the chain of includes that led to it; `leave` must be called once its entries are resolved.
+    fn enter(&mut self, path: &Path) -> Result<Value, Box<dyn std::error::Error>> {
+        let display = path.to_string_lossy().to_string();
+        let canonical = canonicalize(path).map_err(|err| InfcoError::new(&*format!("error reading tasks file \"{}\": {}", display, err)))?;
+
+        if self.stack.iter().any(|(entered, _)| *entered == canonical) {
+            let chain: Vec<&str> = self.stack.iter().map(|(_, display)| &**display).chain(std::iter::once(&*display)).collect();
+
+            return Err(InfcoError::new(&*format!("include cycle: {}", chain.join(" -> "))).into());
+        }
+
+        let content = read_to_string(&canonical).map_err(|err| InfcoError::new(&*format!("error reading tasks file \"{}\": {}", display, err)))?;
+        let value = serde_json::from_str(&*content).map_err(|err| InfcoError::new(&*format!("error parsing tasks file \"{}\": {}", display, err)))?;
+
+        self.stack.push((canonical, display));
+
+        Ok(value)
+    }
+
+    fn leave(&mut self) {
+        self.stack.pop();
+    }
+
+    fn expand_tasks_file(&mut self, content: &Value, path: &Path, role_dir: Option<&Path>) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        self.add_handlers(get_list(&content["handlers"], "handlers", path)?, role_dir)?;
+
+        let tasks = self.expand(get_list(&content["tasks"], "tasks", path)?, dir, role_dir)?;
+
+        self.leave();
+
+        Ok(tasks)
+    }
+
+    fn expand(&mut self, entries: &[Value], dir: &Path, role_dir: Option<&Path>) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+        let mut tasks = Vec::new();
+
+        for entry in entries {
+            match (&entry["include"], &entry["role"]) {
+                (Value::String(include), Value::Null) => {
+                    let path = dir.join(include);
+                    let content = self.enter(&path)?;
+
+                    tasks.extend(add_tags(self.expand_tasks_file(&content, &path, role_dir)?, &entry["tags"]));
+                },
+                (Value::Null, Value::String(role)) => tasks.extend(add_tags(self.expand_role(role)?, &entry["tags"])),
+                (Value::Null, Value::Null) => tasks.push(resolve_local_path(entry, role_dir)),
+                _ => return Err(InfcoError::new(&*format!("task {} must either include a file or a role", entry["title"])).into())
+            }
+        }
+
+        Ok(tasks)
+    }
+
+    fn expand_role(&mut self, name: &str) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+        if name.is_empty() || name.contains(['/', '\\']) || name == ".." {
+            return Err(InfcoError::new(&*format!("invalid role name \"{}\"", name)).into());
+        }
+
+        let role_dir = self.roles_dir.join(name);
+        let tasks_path = role_dir.join("tasks.json");
+        let content = self.enter(&tasks_path)?;
+        let entries = get_list(&content, "tasks", &tasks_path)?;
+        let defaults_path = role_dir.join("defaults.json");
+        let defaults = match defaults_path.exists() {
+            true => {
+                let defaults = self.enter(&defaults_path)?;
+
+                self.leave();
+
+                match defaults {
+                    Value::Object(defaults) => defaults,
+                    _ => return Err(InfcoError::new(&*format!("defaults in \"{}\" must be an object", defaults_path.to_string_lossy())).into())
+                }
+            },
+            false => Map::new()
+        };
+
+        self.role_defaults.push(defaults);
+
+        if !self.roles.iter().any(|role| role == name) {
+            self.roles.push(name.into());
+
+            let handlers_path = role_dir.join("handlers.json");
+
+            if handlers_path.exists() {
+                let handlers = self.enter(&handlers_path)?;
+
+                self.leave();
+                self.add_handlers(get_list(&handlers, "handlers", &handlers_path)?, Some(&role_dir))?;
+            }
+        }
+
+        let tasks = self.expand(entries, &role_dir, Some(&role_dir))?;
+        let tasks = tasks.iter().map(|task| self.add_role_defaults(task)).collect();
+
+        self.role_defaults.pop();
+        self.leave();
+
+        Ok(tasks)
+    }
+
+    /// Adds the handlers unless a handler with the same title has been added already; handlers with the same title must not differ.
+    fn add_handlers(&mut self, handlers: &[Value], role_dir: Option<&Path>) -> Result<(), InfcoError> {
+        for handler in handlers {
+            let handler = self.add_role_defaults(&resolve_local_path(handler, role_dir));
+
+            match self.handlers.iter().find(|added| added["title"] == handler["title"]) {
+                Some(added) if *added == handler => {},
+                Some(_) => return Err(InfcoError::new(&*format!("handler {} is defined more than once with different definitions", handler["title"]))),
+                None => self.handlers.push(handler)
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Attaches the defaults of the innermost role being expanded to a task, unless it already has the defaults of a role nested in it.
+    fn add_role_defaults(&self, task: &Value) -> Value {
+        let mut task = task.clone();
+
+        if let (Some(defaults), Value::Null) = (self.role_defaults.last(), &task["roleDefaults"]) {
+            if !defaults.is_empty() {
+                task["roleDefaults"] = Value::Object(defaults.clone());
+            }
+        }
+
+        task
+    }
+}
+
+fn get_list<'a>(value: &'a Value, name: &str, path: &Path) -> Result<&'a [Value], InfcoError> {
+    match value {
+        Value::Null => Ok(&[]),
+        Value::Array(entries) => Ok(entries),
+        _ => Err(InfcoError::new(&*format!("{} in \"{}\" must be an array", name, path.to_string_lossy())))
+    }
+}
+
+#[test]
+fn function_add_tags() {
+    let tasks = add_tags(vec![json!({"title": "a"}), json!({"title": "b", "tags": ["b"]})], &json!(["web"]));
+
+    assert_eq!(tasks[0]["tags"], json!(["web"]));
+    assert_eq!(tasks[1]["tags"], json!(["b", "web"]));
+    assert_eq!(add_tags(vec![json!({"title": "a"})], &Value::Null)[0], json!({"title": "a"}));
+}
+
+fn add_tags(mut tasks: Vec<Value>, tags: &Value) -> Vec<Value> {
+    if let Value::Array(tags) = tags {
+        for task in &mut tasks {
+            match &mut task["tags"] {
+                Value::Array(task_tags) => task_tags.extend(tags.iter().cloned()),
+                task_tags => *task_tags = Value::Array(tags.clone())
+            }
+        }
+    }
+
+    tasks
+}
+
+#[test]
+fn function_resolve_local_path() {
+    let role_dir = Path::new("roles/web");
+
+    assert_eq!(resolve_local_path(&json!({"type": "template", "config": {"localPath": "a.tpl"}}), Some(role_dir))["config"]["localPath"], "roles/web/templates/a.tpl");
+    assert_eq!(resolve_local_path(&json!({"type": "script", "config": {"localPath": "a.sh"}}), Some(role_dir))["config"]["localPath"], "roles/web/files/a.sh");
+    assert_eq!(resolve_local_path(&json!({"type": "fileTransfer", "config": {"localPath": "a", "direction": "contextToLocal"}}), Some(role_dir))["config"]["localPath"], "a");
+    assert_eq!(resolve_local_path(&json!({"type": "unarchive", "config": {"localPath": "/tmp/a.tar"}}), Some(role_dir))["config"]["localPath"], "/tmp/a.tar");
+    assert_eq!(resolve_local_path(&json!({"type": "template", "config": {"localPath": "a.tpl"}}), None)["config"]["localPath"], "a.tpl");
+}
+
+/// Makes a relative local path of a task of a role refer to the `templates` or `files` directory of the role.
+fn resolve_local_path(task: &Value, role_dir: Option<&Path>) -> Value {
+    let mut task = task.clone();
+    let sub_dir = match (task["type"].as_str(), task["config"]["direction"].as_str()) {
+        (Some("template"), _) => "templates",
+        (Some("fileTransfer"), Some("contextToLocal")) => return task,
+        (Some("fileTransfer"), _) | (Some("script"), _) | (Some("unarchive"), _) => "files",
+        _ => return task
+    };
+
+    if let (Some(role_dir), Some(local_path)) = (role_dir, task["config"]["localPath"].as_str()) {
+        if Path::new(local_path).is_relative() {
+            task["config"]["localPath"] = Value::String(role_dir.join(sub_dir).join(local_path).to_string_lossy().to_string());
+        }
+    }
+
+    task
+}
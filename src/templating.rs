@@ -0,0 +1,4 @@
+mod error;
+pub mod expression;
+mod parser;
+pub mod renderer;
@@ -0,0 +1,25 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result, Debug};
+
+#[derive(Debug)]
+pub struct TemplateError {
+    description : String,
+}
+
+impl TemplateError {
+    pub fn new(description: &str) -> TemplateError {
+        TemplateError {
+            description: description.into()
+        }
+    }
+}
+
+impl Error for TemplateError {
+
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.description)
+    }
+}
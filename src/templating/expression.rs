@@ -0,0 +1,333 @@
+use super::error::TemplateError;
+use super::renderer::{lookup, is_truthy};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+#[derive(Debug, PartialEq)]
+pub enum Expression {
+    Literal(Value),
+    Variable(String),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Compare(Operator, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    In,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Literal(Value),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 12] = ["==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")", "="];
+
+#[test]
+fn function_is_true() {
+    let vars = serde_json::json!({
+        "os": {"family": "debian", "version": 11},
+        "vars": {"role": "db", "tls": false, "ports": [80, 443]}
+    });
+
+    assert!(is_true("os.family == \"debian\" && vars.role == \"db\"", &vars).unwrap());
+    assert!(is_true("os.family == 'redhat' || vars.role != \"web\"", &vars).unwrap());
+    assert!(is_true("not vars.tls and os.version >= 10", &vars).unwrap());
+    assert!(is_true("!(os.version < 11) && 443 in vars.ports", &vars).unwrap());
+    assert!(is_true("vars.ports.1 == 443.0", &vars).unwrap());
+    assert!(!is_true("vars.missing", &vars).unwrap());
+    assert!(!is_true("vars.missing == \"x\"", &vars).unwrap());
+    assert!(is_true("\"de\" in os.family", &vars).unwrap());
+    assert!(is_true("(true || false) && -1 < 0", &vars).unwrap());
+    assert!(is_true("\"10\" > \"9\" && \"9.5\" < \"10\" && \"100\" >= \"100.0\"", &vars).unwrap());
+    assert!(is_true("\"b10\" < \"b9\"", &vars).unwrap());
+    assert_eq!(is_true("os.family < 1", &vars).unwrap_err().to_string(), "cannot compare \"debian\" and 1");
+}
+
+/// Evaluates a condition (e.g. `os.family == "debian" && vars.role == "db"`) against the given variables.
+///
+/// Supported are string, number, boolean and `null` literals, dot-separated variable paths, the comparisons `==`, `!=`, `<`, `<=`, `>`, `>=` and `in`, the logical operators `&&`/`and`, `||`/`or` and `!`/`not` as well as parentheses. Variables that do not exist evaluate to `null`.
+pub fn is_true(condition: &str, vars: &Value) -> Result<bool, TemplateError> {
+    Ok(is_truthy(&evaluate(&parse_expression(condition)?, vars)?))
+}
+
+#[test]
+fn function_parse_expression() {
+    assert_eq!(parse_expression("a.b == 'x'").unwrap(), Expression::Compare(
+        Operator::Equal,
+        Box::new(Expression::Variable("a.b".into())),
+        Box::new(Expression::Literal(Value::from("x")))
+    ));
+    assert_eq!(parse_expression("a || b && !c").unwrap(), Expression::Or(
+        Box::new(Expression::Variable("a".into())),
+        Box::new(Expression::And(
+            Box::new(Expression::Variable("b".into())),
+            Box::new(Expression::Not(Box::new(Expression::Variable("c".into()))))
+        ))
+    ));
+    assert_eq!(parse_expression("a = \"b\"").unwrap_err().to_string(), "malformed expression \"a = \"b\"\": unexpected \"=\"; use \"==\" for comparisons");
+    assert_eq!(parse_expression("a == ").unwrap_err().to_string(), "malformed expression \"a == \": unexpected end of expression");
+    assert_eq!(parse_expression("(a").unwrap_err().to_string(), "malformed expression \"(a\": missing \")\"");
+    assert_eq!(parse_expression("a b").unwrap_err().to_string(), "malformed expression \"a b\": unexpected \"b\"");
+    assert_eq!(parse_expression("\"a").unwrap_err().to_string(), "malformed expression \"\"a\": unterminated string");
+    assert!(parse_expression("").is_err());
+}
+
+/// Parses an expression into a tree; see `is_true` for the supported syntax.
+pub fn parse_expression(source: &str) -> Result<Expression, TemplateError> {
+    let malformed = |reason: &str| TemplateError::new(&*format!("malformed expression \"{}\": {}", source, reason));
+    let tokens = tokenize(source).map_err(|reason| malformed(&reason))?;
+    let mut parser = Parser { tokens, position: 0 };
+    let expression = parser.parse_or().map_err(|reason| malformed(&reason))?;
+
+    match parser.next() {
+        None => Ok(expression),
+        Some(token) => Err(malformed(&*format!("unexpected {}", describe(&token))))
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = source.chars().collect();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        let rest: String = chars[index..].iter().collect();
+
+        if c.is_whitespace() {
+            index += 1;
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+
+            index += 1;
+
+            loop {
+                match chars.get(index) {
+                    None => return Err("unterminated string".into()),
+                    Some('\\') if index + 1 < chars.len() => {
+                        value.push(chars[index + 1]);
+                        index += 2;
+                    },
+                    Some(&quote) if quote == c => {
+                        index += 1;
+                        break;
+                    },
+                    Some(&other) => {
+                        value.push(other);
+                        index += 1;
+                    }
+                }
+            }
+
+            tokens.push(Token::Literal(Value::String(value)));
+        } else if c.is_ascii_digit() || (c == '-' && chars.get(index + 1).is_some_and(|next| next.is_ascii_digit())) {
+            let start = index;
+
+            index += 1;
+
+            while index < chars.len() && (chars[index].is_ascii_digit() || chars[index] == '.') {
+                index += 1;
+            }
+
+            let number: String = chars[start..index].iter().collect();
+            let value = match number.parse::<i64>() {
+                Ok(integer) => Value::from(integer),
+                Err(_) => Value::from(number.parse::<f64>().map_err(|_| format!("malformed number \"{}\"", number))?)
+            };
+
+            tokens.push(Token::Literal(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = index;
+
+            while index < chars.len() && (chars[index].is_alphanumeric() || chars[index] == '_' || chars[index] == '.') {
+                index += 1;
+            }
+
+            let word: String = chars[start..index].iter().collect();
+
+            tokens.push(match &*word {
+                "true" => Token::Literal(Value::Bool(true)),
+                "false" => Token::Literal(Value::Bool(false)),
+                "null" => Token::Literal(Value::Null),
+                "and" => Token::Symbol("&&"),
+                "or" => Token::Symbol("||"),
+                "not" => Token::Symbol("!"),
+                "in" => Token::Symbol("in"),
+                _ => Token::Identifier(word)
+            });
+        } else {
+            let symbol = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)).ok_or(format!("unexpected \"{}\"", c))?;
+
+            if *symbol == "=" {
+                return Err("unexpected \"=\"; use \"==\" for comparisons".into());
+            }
+
+            tokens.push(Token::Symbol(symbol));
+            index += symbol.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Literal(value) => format!("{}", value),
+        Token::Identifier(name) => format!("\"{}\"", name),
+        Token::Symbol(symbol) => format!("\"{}\"", symbol),
+    }
+}
+
+/// A recursive descent parser; `||` binds weaker than `&&`, which binds weaker than `!` and the comparisons.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+
+        self.position += 1;
+        token
+    }
+
+    fn next_is(&mut self, symbol: &str) -> bool {
+        match self.tokens.get(self.position) {
+            Some(Token::Symbol(next)) if *next == symbol => {
+                self.position += 1;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_and()?;
+
+        while self.next_is("||") {
+            expression = Expression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_not()?;
+
+        while self.next_is("&&") {
+            expression = Expression::And(Box::new(expression), Box::new(self.parse_not()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, String> {
+        match self.next_is("!") {
+            true => Ok(Expression::Not(Box::new(self.parse_not()?))),
+            false => self.parse_comparison()
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, String> {
+        let left = self.parse_primary()?;
+        let operator = match self.tokens.get(self.position) {
+            Some(Token::Symbol("==")) => Operator::Equal,
+            Some(Token::Symbol("!=")) => Operator::NotEqual,
+            Some(Token::Symbol("<")) => Operator::Less,
+            Some(Token::Symbol("<=")) => Operator::LessOrEqual,
+            Some(Token::Symbol(">")) => Operator::Greater,
+            Some(Token::Symbol(">=")) => Operator::GreaterOrEqual,
+            Some(Token::Symbol("in")) => Operator::In,
+            _ => return Ok(left)
+        };
+
+        self.position += 1;
+
+        Ok(Expression::Compare(operator, Box::new(left), Box::new(self.parse_primary()?)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Literal(value)) => Ok(Expression::Literal(value)),
+            Some(Token::Identifier(path)) => Ok(Expression::Variable(path)),
+            Some(Token::Symbol("(")) => {
+                let expression = self.parse_or()?;
+
+                match self.next_is(")") {
+                    true => Ok(expression),
+                    false => Err("missing \")\"".into())
+                }
+            },
+            Some(token) => Err(format!("unexpected {}", describe(&token))),
+            None => Err("unexpected end of expression".into())
+        }
+    }
+}
+
+/// Evaluates an expression; comparisons and logical operators result in booleans.
+pub fn evaluate(expression: &Expression, vars: &Value) -> Result<Value, TemplateError> {
+    match expression {
+        Expression::Literal(value) => Ok(value.clone()),
+        Expression::Variable(path) => Ok(lookup(vars, path).cloned().unwrap_or(Value::Null)),
+        Expression::Not(inner) => Ok(Value::Bool(!is_truthy(&evaluate(inner, vars)?))),
+        Expression::And(left, right) => Ok(Value::Bool(is_truthy(&evaluate(left, vars)?) && is_truthy(&evaluate(right, vars)?))),
+        Expression::Or(left, right) => Ok(Value::Bool(is_truthy(&evaluate(left, vars)?) || is_truthy(&evaluate(right, vars)?))),
+        Expression::Compare(operator, left, right) => {
+            let left = evaluate(left, vars)?;
+            let right = evaluate(right, vars)?;
+
+            Ok(Value::Bool(match operator {
+                Operator::Equal => is_equal(&left, &right),
+                Operator::NotEqual => !is_equal(&left, &right),
+                Operator::In => match &right {
+                    Value::Array(vec) => vec.iter().any(|entry| is_equal(&left, entry)),
+                    Value::Object(map) => left.as_str().is_some_and(|key| map.contains_key(key)),
+                    Value::String(s) => left.as_str().is_some_and(|part| s.contains(part)),
+                    _ => false
+                },
+                operator => {
+                    let ordering = compare(&left, &right).ok_or(TemplateError::new(&*format!("cannot compare {} and {}", left, right)))?;
+
+                    match operator {
+                        Operator::Less => ordering == Ordering::Less,
+                        Operator::LessOrEqual => ordering != Ordering::Greater,
+                        Operator::Greater => ordering == Ordering::Greater,
+                        _ => ordering != Ordering::Less
+                    }
+                }
+            }))
+        }
+    }
+}
+
+/// Compares two values; numbers are compared by value, regardless of whether they are integers or floats.
+fn is_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.as_f64() == right.as_f64(),
+        (left, right) => left == right
+    }
+}
+
+/// Orders two values; strings are compared numerically if both of them are numbers (e.g. versions read from facts), otherwise lexicographically.
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.as_f64()?.partial_cmp(&right.as_f64()?),
+        (Value::String(left), Value::String(right)) => match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+            (Ok(left), Ok(right)) => left.partial_cmp(&right),
+            _ => Some(left.cmp(right))
+        },
+        _ => None
+    }
+}
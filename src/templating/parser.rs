@@ -0,0 +1,179 @@
+use super::error::TemplateError;
+
+#[derive(Debug, PartialEq)]
+pub enum Node {
+    Text(String),
+    Variable(String),
+    If { condition: String, then_nodes: Vec<Node>, else_nodes: Vec<Node> },
+    For { item: String, list: String, nodes: Vec<Node> },
+}
+
+enum Token {
+    Text(String),
+    Variable(String),
+    Tag(String),
+}
+
+#[test]
+fn function_parse() {
+    assert_eq!(parse("a {{ b }} c").unwrap(), vec![
+        Node::Text("a ".into()),
+        Node::Variable("b".into()),
+        Node::Text(" c".into())
+    ]);
+    assert_eq!(parse("{% if a %}\nx\n{% else %}\ny\n{% endif %}\n").unwrap(), vec![
+        Node::If { condition: "a".into(), then_nodes: vec![Node::Text("x\n".into())], else_nodes: vec![Node::Text("y\n".into())] }
+    ]);
+    assert_eq!(parse("{% for i in list %}{{ i }}{% endfor %}").unwrap(), vec![
+        Node::For { item: "i".into(), list: "list".into(), nodes: vec![Node::Variable("i".into())] }
+    ]);
+    assert_eq!(parse("a {% raw %}{{ b }} {% if %}{%endraw%} c").unwrap(), vec![
+        Node::Text("a ".into()),
+        Node::Text("{{ b }} {% if %}".into()),
+        Node::Text(" c".into())
+    ]);
+    assert!(parse("{% raw %}{{ b }}").is_err());
+    assert!(parse("{{ a ").is_err());
+    assert!(parse("{% if a %}").is_err());
+    assert!(parse("{% endfor %}").is_err());
+    assert!(parse("{% for i of list %}{% endfor %}").is_err());
+}
+
+/// Parses a template into a tree of nodes.
+///
+/// A newline directly following a `{% ... %}` tag is dropped, so that tags on lines of their own do not leave empty lines in the output.
+/// Text between `{% raw %}` and `{% endraw %}` is taken literally, so that it may contain `{{` and `{%`.
+pub fn parse(template: &str) -> Result<Vec<Node>, TemplateError> {
+    let tokens = tokenize(template)?;
+    let mut iter = tokens.into_iter();
+    let (nodes, end) = parse_nodes(&mut iter)?;
+
+    match end {
+        None => Ok(nodes),
+        Some(tag) => Err(TemplateError::new(&*format!("unexpected tag \"{}\"", tag)))
+    }
+}
+
+fn tokenize(template: &str) -> Result<Vec<Token>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+
+    while !rest.is_empty() {
+        let start = match (rest.find("{{"), rest.find("{%")) {
+            (Some(var), Some(tag)) => Some(var.min(tag)),
+            (Some(var), None) => Some(var),
+            (None, Some(tag)) => Some(tag),
+            (None, None) => None
+        };
+
+        match start {
+            None => {
+                tokens.push(Token::Text(rest.into()));
+                break;
+            },
+            Some(start) => {
+                if start > 0 {
+                    tokens.push(Token::Text(rest[..start].into()));
+                }
+
+                let is_tag = rest[start..].starts_with("{%");
+                let closing = if is_tag { "%}" } else { "}}" };
+                let content_start = start + 2;
+                let content_len = rest[content_start..].find(closing).ok_or(TemplateError::new(&*format!("unclosed \"{}\"", &rest[start..content_start])))?;
+                let content = rest[content_start..content_start + content_len].trim();
+
+                rest = &rest[content_start + content_len + 2..];
+
+                if is_tag && content == "raw" {
+                    let (text, after) = split_raw(rest.strip_prefix('\n').unwrap_or(rest))?;
+
+                    tokens.push(Token::Text(text.into()));
+                    rest = after.strip_prefix('\n').unwrap_or(after);
+                } else if is_tag {
+                    tokens.push(Token::Tag(content.into()));
+                    rest = rest.strip_prefix('\n').unwrap_or(rest);
+                } else {
+                    tokens.push(Token::Variable(content.into()));
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Splits the input following a `{% raw %}` tag into the literal text and the input following the matching `{% endraw %}` tag.
+fn split_raw(input: &str) -> Result<(&str, &str), TemplateError> {
+    let mut offset = 0;
+
+    while let Some(start) = input[offset..].find("{%").map(|start| offset + start) {
+        let content_len = match input[start + 2..].find("%}") {
+            Some(content_len) => content_len,
+            None => break
+        };
+
+        if input[start + 2..start + 2 + content_len].trim() == "endraw" {
+            return Ok((&input[..start], &input[start + 2 + content_len + 2..]));
+        }
+
+        offset = start + 2;
+    }
+
+    Err(TemplateError::new("missing \"endraw\" for \"raw\""))
+}
+
+/// Parses nodes until the end of the input or an end/else tag, which is returned to the caller.
+fn parse_nodes(iter: &mut std::vec::IntoIter<Token>) -> Result<(Vec<Node>, Option<String>), TemplateError> {
+    let mut nodes = Vec::new();
+
+    while let Some(token) = iter.next() {
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text)),
+            Token::Variable(name) => nodes.push(Node::Variable(name)),
+            Token::Tag(tag) => {
+                let (keyword, args) = match tag.find(char::is_whitespace) {
+                    Some(pos) => (&tag[..pos], tag[pos..].trim()),
+                    None => (&*tag, "")
+                };
+
+                match keyword {
+                    "if" => {
+                        let (then_nodes, end) = parse_nodes(iter)?;
+                        let else_nodes = match end.as_deref() {
+                            Some("endif") => Vec::new(),
+                            Some("else") => {
+                                let (else_nodes, end) = parse_nodes(iter)?;
+
+                                match end.as_deref() {
+                                    Some("endif") => else_nodes,
+                                    _ => return Err(TemplateError::new(&*format!("missing \"endif\" for \"if {}\"", args)))
+                                }
+                            },
+                            _ => return Err(TemplateError::new(&*format!("missing \"endif\" for \"if {}\"", args)))
+                        };
+
+                        nodes.push(Node::If { condition: args.into(), then_nodes, else_nodes });
+                    },
+                    "for" => {
+                        let parts: Vec<&str> = args.split_whitespace().collect();
+
+                        if parts.len() != 3 || parts[1] != "in" {
+                            return Err(TemplateError::new(&*format!("malformed loop \"for {}\"", args)));
+                        }
+
+                        let (loop_nodes, end) = parse_nodes(iter)?;
+
+                        match end.as_deref() {
+                            Some("endfor") => nodes.push(Node::For { item: parts[0].into(), list: parts[2].into(), nodes: loop_nodes }),
+                            _ => return Err(TemplateError::new(&*format!("missing \"endfor\" for \"for {}\"", args)))
+                        }
+                    },
+                    "else" | "endif" | "endfor" => return Ok((nodes, Some(keyword.into()))),
+                    _ => return Err(TemplateError::new(&*format!("unknown tag \"{}\"", keyword)))
+                }
+            }
+        }
+    }
+
+    Ok((nodes, None))
+}
@@ -0,0 +1,130 @@
+use super::error::TemplateError;
+use super::expression::is_true;
+use super::parser::{parse, Node};
+use serde_json::Value;
+use std::error::Error;
+
+#[test]
+fn function_render() {
+    let vars = serde_json::json!({
+        "name": "web1",
+        "port": 8080,
+        "tls": true,
+        "upstreams": [{"host": "a"}, {"host": "b"}]
+    });
+
+    assert_eq!(render("server {{ name }}:{{ port }}", &vars).unwrap(), "server web1:8080");
+    assert_eq!(render("{% if tls %}\nssl on;\n{% else %}\nssl off;\n{% endif %}\n", &vars).unwrap(), "ssl on;\n");
+    assert_eq!(render("{% if not tls %}x{% else %}y{% endif %}", &vars).unwrap(), "y");
+    assert_eq!(render("{% if tls && port == 8080 %}x{% endif %}", &vars).unwrap(), "x");
+    assert_eq!(render("{% for u in upstreams %}\n{{ u.host }};\n{% endfor %}\n", &vars).unwrap(), "a;\nb;\n");
+    assert_eq!(render("{{ upstreams.1.host }}", &vars).unwrap(), "b");
+    assert_eq!(render("{{ missing.value }}", &vars).unwrap_err().to_string(), "variable \"missing.value\" not found");
+}
+
+/// Renders a template with the given variables.
+///
+/// Supported are variables (`{{ a.b }}`), conditionals (`{% if a == "b" %}`, `{% else %}`, `{% endif %}`; see `expression::is_true` for the syntax of conditions), loops over arrays (`{% for i in a %}`, `{% endfor %}`) and literal text (`{% raw %}{{ a }}{% endraw %}`).
+pub fn render(template: &str, vars: &Value) -> Result<String, Box<dyn Error>> {
+    let nodes = parse(template)?;
+    let mut output = String::new();
+
+    render_nodes(&nodes, vars, &mut output)?;
+
+    Ok(output)
+}
+
+#[test]
+fn function_render_value() {
+    let vars = serde_json::json!({"item": {"name": "svc", "uid": 990}});
+    let value = serde_json::json!({"name": "{{ item.name }}", "uid": "{{ item.uid }}", "home": "/srv/{{ item.name }}", "groups": ["{{ item.name }}"], "system": true});
+
+    assert_eq!(render_value(&value, &vars).unwrap(), serde_json::json!({"name": "svc", "uid": 990, "home": "/srv/svc", "groups": ["svc"], "system": true}));
+
+    let command = serde_json::json!({"command": "docker ps --format '{% raw %}{{.Names}}{% endraw %}' --filter name={{ item.name }}"});
+
+    assert_eq!(render_value(&command, &vars).unwrap(), serde_json::json!({"command": "docker ps --format '{{.Names}}' --filter name=svc"}));
+    assert!(render_value(&serde_json::json!({"command": "docker ps --format '{{.Names}}'"}), &vars).is_err());
+}
+
+/// Renders all strings contained in a value as templates.
+///
+/// A string consisting of a single variable only (e.g. `"{{ item }}"`) is replaced by the value of the variable, so that numbers, arrays and objects keep their type.
+pub fn render_value(value: &Value, vars: &Value) -> Result<Value, Box<dyn Error>> {
+    match value {
+        Value::String(s) => match &*parse(s)? {
+            [Node::Variable(path)] => Ok(get_variable(vars, path)?.clone()),
+            _ => Ok(Value::String(render(s, vars)?))
+        },
+        Value::Array(vec) => Ok(Value::Array(vec.iter().map(|entry| render_value(entry, vars)).collect::<Result<Vec<Value>, Box<dyn Error>>>()?)),
+        Value::Object(map) => {
+            let mut rendered = serde_json::Map::new();
+
+            for (key, entry) in map {
+                rendered.insert(key.clone(), render_value(entry, vars)?);
+            }
+
+            Ok(Value::Object(rendered))
+        },
+        value => Ok(value.clone())
+    }
+}
+
+/// Looks up a dot-separated path (e.g. `host.interfaces.0.name`) in the given variables.
+pub fn lookup<'a>(vars: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut value = vars;
+
+    for key in path.split('.') {
+        value = match value {
+            Value::Object(map) => map.get(key)?,
+            Value::Array(vec) => vec.get(key.parse::<usize>().ok()?)?,
+            _ => return None
+        };
+    }
+
+    Some(value)
+}
+
+/// Evaluates the truthiness of a value; null, false, zero and empty strings, arrays and objects are false.
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(vec) => !vec.is_empty(),
+        Value::Object(map) => !map.is_empty()
+    }
+}
+
+fn render_nodes(nodes: &[Node], vars: &Value, output: &mut String) -> Result<(), TemplateError> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Variable(path) => match get_variable(vars, path)? {
+                Value::String(s) => output.push_str(s),
+                Value::Null => {},
+                value => output.push_str(&value.to_string())
+            },
+            Node::If { condition, then_nodes, else_nodes } => {
+                render_nodes(if is_true(condition, vars)? { then_nodes } else { else_nodes }, vars, output)?;
+            },
+            Node::For { item, list, nodes } => {
+                let entries = get_variable(vars, list)?.as_array().ok_or(TemplateError::new(&*format!("variable \"{}\" is not a list", list)))?;
+
+                for entry in entries {
+                    let mut loop_vars = vars.clone();
+
+                    loop_vars[item.as_str()] = entry.clone();
+                    render_nodes(nodes, &loop_vars, output)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn get_variable<'a>(vars: &'a Value, path: &str) -> Result<&'a Value, TemplateError> {
+    lookup(vars, path).ok_or(TemplateError::new(&*format!("variable \"{}\" not found", path)))
+}